@@ -0,0 +1,241 @@
+use crate::structs::{Note, TXTSong};
+use crate::validate::{normalized_text, previous_notes, sung_notes, validate, Lint, LintKind};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Describes the repairs the autofix applies to a song
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FixKind {
+    /// the notes of a line were sorted by their start
+    SortedNotes,
+    /// a note was shortened so it ends before the next note starts
+    ShortenedNote {
+        /// the duration before the fix
+        from: i32,
+        /// the duration after the fix
+        to: i32,
+    },
+    /// a line break was moved between the last note of the previous line and the first note
+    MovedLineBreak {
+        /// the beat of the line break before the fix, as written in the song
+        from: i32,
+        /// the beat of the line break after the fix, as written in the song
+        to: i32,
+    },
+    /// whitespace was removed from the end of the text of a note
+    TrimmedText,
+    /// the text of a note that continues the previous syllable was replaced by "~"
+    NormalizedContinuation,
+}
+
+impl FixKind {
+    /// Returns the code of the fix
+    ///
+    /// The codes are stable and can be used to filter fixes.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            FixKind::SortedNotes => "sorted-notes",
+            FixKind::ShortenedNote { .. } => "shortened-note",
+            FixKind::MovedLineBreak { .. } => "moved-line-break",
+            FixKind::TrimmedText => "trimmed-text",
+            FixKind::NormalizedContinuation => "normalized-continuation",
+        }
+    }
+}
+
+impl fmt::Display for FixKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FixKind::SortedNotes => write!(f, "sorted the notes of the line"),
+            FixKind::ShortenedNote { from, to } => {
+                write!(f, "shortened the note from {} to {} beats", from, to)
+            }
+            FixKind::MovedLineBreak { from, to } => {
+                write!(f, "moved the line break from {} to {}", from, to)
+            }
+            FixKind::TrimmedText => write!(f, "removed trailing whitespace"),
+            FixKind::NormalizedContinuation => write!(f, "normalized the continuation"),
+        }
+    }
+}
+
+/// Describes a repair the autofix applied to a song and where it was applied
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Fix {
+    /// the kind of the repair
+    pub kind: FixKind,
+    /// the index of the line in the lines of the song after the fix
+    pub line: usize,
+    /// the index of the note in the notes of the line after the fix, None for the whole line
+    pub note: Option<usize>,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] line {}", self.kind.code(), self.line)?;
+        if let Some(note) = self.note {
+            write!(f, ", note {}", note)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+// returns true for notes that are sung and have a start and a duration
+fn is_singable(note: &Note) -> bool {
+    note.start().is_some()
+}
+
+// returns the lines that have a lint of one of the given kinds
+fn linted_lines(lints: &[Lint], kinds: &[LintKind]) -> Vec<usize> {
+    let mut lines: Vec<usize> = lints
+        .iter()
+        .filter(|lint| kinds.contains(&lint.kind))
+        .map(|lint| lint.line)
+        .collect();
+    lines.dedup();
+    lines
+}
+
+// sorts the runs of singable notes of lines with notes out of order,
+// player and bpm changes stay where they are
+fn sort_notes(song: &mut TXTSong, lints: &[Lint], fixes: &mut Vec<Fix>) {
+    for line_index in linted_lines(lints, &[LintKind::NotesOutOfOrder]) {
+        let line = &mut song.lines[line_index];
+        let mut sorted = false;
+        for run in line.notes.split_mut(|note| !is_singable(note)) {
+            if run.windows(2).any(|x| x[0].start() > x[1].start()) {
+                run.sort_by_key(|note| note.start());
+                sorted = true;
+            }
+        }
+        if sorted {
+            fixes.push(Fix {
+                kind: FixKind::SortedNotes,
+                line: line_index,
+                note: None,
+            });
+        }
+    }
+}
+
+// trims the texts of the notes and normalizes continuations
+fn fix_texts(song: &mut TXTSong, lints: &[Lint], fixes: &mut Vec<Fix>) {
+    for lint in lints.iter() {
+        let kind = match lint.kind {
+            LintKind::TrailingWhitespace => FixKind::TrimmedText,
+            LintKind::NonStandardContinuation => FixKind::NormalizedContinuation,
+            _ => continue,
+        };
+        let note_index = lint.note.expect("text lints belong to a note");
+        let line = &mut song.lines[lint.line];
+        let last_in_line = line.notes.iter().rposition(is_singable) == Some(note_index);
+        if let Some(text) = line.notes[note_index].text_mut() {
+            *text = normalized_text(text, last_in_line);
+            fixes.push(Fix {
+                kind,
+                line: lint.line,
+                note: lint.note,
+            });
+        }
+    }
+}
+
+// shortens notes that overlap the next note of the same player
+fn fix_overlaps(song: &mut TXTSong, lints: &[Lint], fixes: &mut Vec<Fix>) {
+    let notes = sung_notes(song);
+    let previous = previous_notes(&notes);
+    for lint in lints.iter() {
+        if lint.kind != LintKind::OverlappingNotes {
+            continue;
+        }
+        let index = notes
+            .iter()
+            .position(|note| note.line == lint.line && Some(note.note) == lint.note)
+            .expect("lints belong to sung notes");
+        let previous_note = match previous[index] {
+            Some(x) => &notes[x],
+            None => continue,
+        };
+        let new_duration = notes[index].start - previous_note.start;
+        // notes that start together need a decision of the author
+        if new_duration <= 0 {
+            continue;
+        }
+        let note = &mut song.lines[previous_note.line].notes[previous_note.note];
+        if let Some(duration) = note.duration_mut() {
+            fixes.push(Fix {
+                kind: FixKind::ShortenedNote {
+                    from: *duration,
+                    to: new_duration,
+                },
+                line: previous_note.line,
+                note: Some(previous_note.note),
+            });
+            *duration = new_duration;
+        }
+    }
+}
+
+// moves line breaks that are not placed between the notes they separate
+fn fix_line_breaks(song: &mut TXTSong, lints: &[Lint], fixes: &mut Vec<Fix>) {
+    let kinds = [
+        LintKind::LineBreakAfterNote,
+        LintKind::LineBreakBeforeNoteEnd,
+    ];
+    let notes = sung_notes(song);
+    for line_index in linted_lines(lints, &kinds) {
+        let previous_end = notes
+            .iter()
+            .rev()
+            .find(|note| note.line + 1 == line_index)
+            .map(|note| note.end);
+        let first_start = match notes.iter().find(|note| note.line == line_index) {
+            Some(note) => note.start,
+            None => continue,
+        };
+        let new_line_break = previous_end.map_or(first_start, |end| end.min(first_start));
+        let previous_origin = song.line_offset(line_index - 1);
+        let line = &mut song.lines[line_index];
+        let from = line.start;
+        line.start = new_line_break - previous_origin;
+        fixes.push(Fix {
+            kind: FixKind::MovedLineBreak {
+                from,
+                to: line.start,
+            },
+            line: line_index,
+            note: None,
+        });
+    }
+}
+
+/// Repairs common mechanical problems of a song and reports every repair
+///
+/// The song is validated and the lints that have a mechanical repair are fixed in this order:
+/// * notes-out-of-order: the notes of the line are sorted by their start
+/// * trailing-whitespace: whitespace at the end of a line is removed,
+///   whitespace after a word is reduced to one space
+/// * non-standard-continuation: continuations like "-" or " ~ " are replaced by "~"
+/// * overlapping-notes: the previous note of the same player is shortened
+/// * line-break-after-note and line-break-before-note-end: the line break is moved
+///   to the end of the last note of the previous line
+///
+/// Problems that need a decision, like notes without duration, are left for the author.
+/// The fixes are sorted by their location in the song.
+///
+/// # Arguments
+/// * song - the song to repair
+///
+pub fn autofix(song: &mut TXTSong) -> Vec<Fix> {
+    let mut fixes = Vec::new();
+    sort_notes(song, &validate(song), &mut fixes);
+    // sorting moves the notes, so the song is validated again before every step
+    fix_texts(song, &validate(song), &mut fixes);
+    fix_overlaps(song, &validate(song), &mut fixes);
+    fix_line_breaks(song, &validate(song), &mut fixes);
+    fixes.sort_by_key(|fix| (fix.line, fix.note.is_none(), fix.note));
+    fixes
+}
@@ -0,0 +1,213 @@
+use crate::generator::{note_line, GeneratorOptions};
+use crate::parser::{parse_note_str, parse_txt_song_str};
+use crate::structs::{Note, TXTSong};
+use std::fmt;
+
+error_chain! {
+    errors {
+        #[doc="the document has no note with the given index"]
+        NoteNotFound(index: usize) {
+            description("note not found")
+            display("the document has no note with index: {}", index)
+        }
+        #[doc="error in parsing the song of the document"]
+        SongParsingError {
+            description("song parsing error")
+        }
+    }
+}
+
+/// Describes a single line of a song file exactly as it was read
+#[derive(PartialEq, Clone, Debug)]
+pub struct DocumentLine {
+    /// the text of the line without its line ending
+    pub text: String,
+    /// the line ending of the line, empty for a last line without line ending
+    pub ending: String,
+}
+
+/// Describes an Ultrastar song file as the lines it is made of
+///
+/// Other than TXTSong, the document keeps the file as it is, including the order of the tags,
+/// the formatting of numbers, line endings and everything after the end indicator.
+/// Editing a tag or a note only rewrites its own line, so writing the document back
+/// leaves the rest of the file byte-identical.
+#[derive(PartialEq, Clone, Debug)]
+pub struct TXTDocument {
+    bom: bool,
+    lines: Vec<DocumentLine>,
+}
+
+impl TXTDocument {
+    /// Splits a song file into its lines
+    ///
+    /// # Arguments
+    /// * txt_str  - a &str that contains the song file
+    ///
+    pub fn parse(txt_str: &str) -> Self {
+        let bom = txt_str.starts_with('\u{feff}');
+        let txt_str = txt_str.trim_start_matches('\u{feff}');
+        let lines = txt_str
+            .split_inclusive('\n')
+            .map(|line| {
+                let text = line.trim_end_matches('\n').trim_end_matches('\r');
+                DocumentLine {
+                    text: String::from(text),
+                    ending: String::from(&line[text.len()..]),
+                }
+            })
+            .collect();
+        TXTDocument { bom, lines }
+    }
+
+    /// returns the lines of the document
+    pub fn lines(&self) -> &[DocumentLine] {
+        &self.lines
+    }
+
+    /// returns the lines of the document for editing them directly
+    pub fn lines_mut(&mut self) -> &mut Vec<DocumentLine> {
+        &mut self.lines
+    }
+
+    /// parses the document into a TXTSong
+    pub fn to_song(&self) -> Result<TXTSong> {
+        parse_txt_song_str(&self.to_string()).chain_err(|| ErrorKind::SongParsingError)
+    }
+
+    // returns the number of lines that belong to the header
+    fn header_len(&self) -> usize {
+        self.lines
+            .iter()
+            .position(|line| !line.text.is_empty() && !line.text.starts_with('#'))
+            .unwrap_or(self.lines.len())
+    }
+
+    // returns the index of the line of the given tag
+    fn tag_index(&self, key: &str) -> Option<usize> {
+        self.lines[..self.header_len()]
+            .iter()
+            .position(|line| split_tag(&line.text).map(|(k, _)| k) == Some(key))
+    }
+
+    /// returns the value of a tag as it is written in the document
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        let index = self.tag_index(key)?;
+        split_tag(&self.lines[index].text).map(|(_, value)| value)
+    }
+
+    /// returns the tags of the document in the order they are written
+    pub fn tags(&self) -> Vec<(&str, &str)> {
+        self.lines[..self.header_len()]
+            .iter()
+            .filter_map(|line| split_tag(&line.text))
+            .collect()
+    }
+
+    /// Sets the value of a tag
+    ///
+    /// An existing tag is changed in its line, a new tag is added after the last tag.
+    ///
+    /// # Arguments
+    /// * key - the name of the tag
+    /// * value - the new value of the tag
+    ///
+    pub fn set_tag(&mut self, key: &str, value: &str) {
+        let text = format!("#{}:{}", key, value);
+        if let Some(index) = self.tag_index(key) {
+            self.lines[index].text = text;
+            return;
+        }
+        let index = self.lines[..self.header_len()]
+            .iter()
+            .rposition(|line| line.text.starts_with('#'))
+            .map_or(0, |x| x + 1);
+        let ending = self.line_ending();
+        let mut new_line = DocumentLine { text, ending };
+        if index > 0 && self.lines[index - 1].ending.is_empty() {
+            // the tag is added after the last line of the file
+            std::mem::swap(&mut new_line.ending, &mut self.lines[index - 1].ending);
+        }
+        self.lines.insert(index, new_line);
+    }
+
+    /// removes a tag and returns its value
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        let index = self.tag_index(key)?;
+        let line = self.lines.remove(index);
+        split_tag(&line.text).map(|(_, value)| String::from(value))
+    }
+
+    // returns the indices of the lines that are notes in the model of the song
+    fn note_indices(&self) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for (index, line) in self.lines.iter().enumerate().skip(self.header_len()) {
+            match line.text.chars().next() {
+                Some('E') => break,
+                Some(':') | Some('*') | Some('F') | Some('R') | Some('G') | Some('P')
+                | Some('B') => indices.push(index),
+                _ => {}
+            }
+        }
+        indices
+    }
+
+    /// Parses a single note of the document
+    ///
+    /// The notes are counted like the notes of all lines of the parsed TXTSong,
+    /// including player changes and bpm changes.
+    ///
+    /// # Arguments
+    /// * index - the index of the note
+    ///
+    pub fn note(&self, index: usize) -> Result<Note> {
+        let line_index = match self.note_indices().get(index) {
+            Some(&x) => x,
+            None => bail!(ErrorKind::NoteNotFound(index)),
+        };
+        parse_note_str(&self.lines[line_index].text, line_index as u32 + 1)
+            .chain_err(|| ErrorKind::SongParsingError)
+    }
+
+    /// Replaces a single note of the document, only the line of the note is rewritten
+    ///
+    /// # Arguments
+    /// * index - the index of the note, counted like in `note`
+    /// * note - the new note
+    ///
+    pub fn set_note(&mut self, index: usize, note: &Note) -> Result<()> {
+        let line_index = match self.note_indices().get(index) {
+            Some(&x) => x,
+            None => bail!(ErrorKind::NoteNotFound(index)),
+        };
+        self.lines[line_index].text = note_line(note, &GeneratorOptions::default());
+        Ok(())
+    }
+
+    // returns the line ending used by the document
+    fn line_ending(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.ending.clone())
+            .find(|ending| !ending.is_empty())
+            .unwrap_or_else(|| String::from("\n"))
+    }
+}
+
+impl fmt::Display for TXTDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.bom {
+            write!(f, "\u{feff}")?;
+        }
+        for line in self.lines.iter() {
+            write!(f, "{}{}", line.text, line.ending)?;
+        }
+        Ok(())
+    }
+}
+
+// splits a header line into the name and the value of its tag
+fn split_tag(text: &str) -> Option<(&str, &str)> {
+    let mut parts = text.strip_prefix('#')?.splitn(2, ':');
+    Some((parts.next()?, parts.next()?))
+}
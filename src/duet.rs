@@ -0,0 +1,208 @@
+use crate::structs::{Header, Line, Note, TXTSong};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Describes who sings a part of a duet
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Player {
+    /// the first player (P1)
+    One,
+    /// the second player (P2)
+    Two,
+    /// both players at once (P3)
+    Both,
+}
+
+impl Player {
+    /// returns the player of a player change number
+    pub fn from_number(number: i32) -> Option<Self> {
+        match number {
+            1 => Some(Player::One),
+            2 => Some(Player::Two),
+            3 => Some(Player::Both),
+            _ => None,
+        }
+    }
+
+    /// returns the number of the player used by player changes
+    pub fn number(self) -> i32 {
+        match self {
+            Player::One => 1,
+            Player::Two => 2,
+            Player::Both => 3,
+        }
+    }
+}
+
+/// Describes the lines a single player sings in a duet
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Debug)]
+pub struct DuetTrack {
+    /// the player that sings the track
+    pub player: Player,
+    /// the name of the singer from the header, the track of both players has no singer
+    pub singer: Option<String>,
+    /// the lines of the track with absolute timing and without player changes
+    pub lines: Vec<Line>,
+}
+
+impl DuetTrack {
+    fn new(player: Player, singer: Option<String>) -> Self {
+        DuetTrack {
+            player,
+            singer,
+            lines: Vec::new(),
+        }
+    }
+
+    // adds the notes of a part of a line to the track
+    fn push_notes(&mut self, start: i32, notes: Vec<Note>) {
+        let start = if self.lines.is_empty() { 0 } else { start };
+        self.lines.push(Line {
+            start,
+            rel: None,
+            notes,
+        });
+    }
+}
+
+/// Describes a duet with a separate track for every player
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Debug)]
+pub struct DuetSong {
+    /// the header of the song
+    pub header: Header,
+    /// the track of the first player
+    pub p1: DuetTrack,
+    /// the track of the second player
+    pub p2: DuetTrack,
+    /// the track both players sing together
+    pub both: DuetTrack,
+}
+
+impl DuetSong {
+    /// returns the track of a player
+    pub fn track(&self, player: Player) -> &DuetTrack {
+        match player {
+            Player::One => &self.p1,
+            Player::Two => &self.p2,
+            Player::Both => &self.both,
+        }
+    }
+
+    /// returns the track of a player for editing it
+    pub fn track_mut(&mut self, player: Player) -> &mut DuetTrack {
+        match player {
+            Player::One => &mut self.p1,
+            Player::Two => &mut self.p2,
+            Player::Both => &mut self.both,
+        }
+    }
+
+    /// Returns all lines a player has to sing, including the lines of both players
+    ///
+    /// The lines are sorted by the start of their first note.
+    pub fn sung_by(&self, player: Player) -> Vec<&Line> {
+        let mut lines: Vec<&Line> = self.track(player).lines.iter().collect();
+        if player != Player::Both {
+            lines.extend(self.both.lines.iter());
+        }
+        lines.sort_by_key(|line| line.notes.iter().find_map(|note| note.start()));
+        lines
+    }
+
+    /// Converts the duet back into a song in the P1/P2 layout of the txt format
+    ///
+    /// The tracks are written one after the other, every track starts with its player change.
+    /// The singer names of the tracks are written to the header.
+    pub fn to_song(&self) -> TXTSong {
+        let mut header = self.header.clone();
+        header.duet_singer_p1 = self.p1.singer.clone();
+        header.duet_singer_p2 = self.p2.singer.clone();
+
+        let mut lines: Vec<Line> = Vec::new();
+        for track in [&self.p1, &self.p2, &self.both].iter() {
+            let mut track_lines = track.lines.iter().cloned();
+            let mut first_line = match track_lines.next() {
+                Some(x) => x,
+                None => continue,
+            };
+            first_line.notes.insert(
+                0,
+                Note::PlayerChange {
+                    player: track.player.number(),
+                },
+            );
+            // the first line of a track continues the last line of the previous track
+            match lines.last_mut() {
+                Some(last_line) => last_line.notes.append(&mut first_line.notes),
+                None => lines.push(first_line),
+            }
+            lines.extend(track_lines);
+        }
+        TXTSong { header, lines }
+    }
+}
+
+impl TXTSong {
+    /// returns true if the song contains player changes
+    pub fn is_duet(&self) -> bool {
+        self.lines
+            .iter()
+            .flat_map(|line| line.notes.iter())
+            .any(|note| note.player().is_some())
+    }
+
+    /// Splits a duet into the tracks of its players
+    ///
+    /// The tracks use absolute timing. Notes before the first player change belong to
+    /// the first player. Returns None if the song is not a duet.
+    pub fn to_duet(&self) -> Option<DuetSong> {
+        if !self.is_duet() {
+            return None;
+        }
+        let song = self.to_absolute();
+        let mut duet = DuetSong {
+            p1: DuetTrack::new(Player::One, song.header.duet_singer_p1.clone()),
+            p2: DuetTrack::new(Player::Two, song.header.duet_singer_p2.clone()),
+            both: DuetTrack::new(Player::Both, None),
+            header: song.header,
+        };
+        duet.header.duet_singer_p1 = None;
+        duet.header.duet_singer_p2 = None;
+
+        let mut player = Player::One;
+        for line in song.lines {
+            // a part that starts in the middle of a line has no line break of its own
+            let mut line_start = Some(line.start);
+            let mut notes = Vec::new();
+            for note in line.notes {
+                match note {
+                    Note::PlayerChange { player: number } => {
+                        if !notes.is_empty() {
+                            let start = part_start(&mut line_start, &notes);
+                            duet.track_mut(player)
+                                .push_notes(start, std::mem::take(&mut notes));
+                        }
+                        player = Player::from_number(number).unwrap_or(player);
+                    }
+                    note => notes.push(note),
+                }
+            }
+            if !notes.is_empty() {
+                let start = part_start(&mut line_start, &notes);
+                duet.track_mut(player).push_notes(start, notes);
+            }
+        }
+        Some(duet)
+    }
+}
+
+// returns the line break of a part of a line, the line break of the line is used only once
+fn part_start(line_start: &mut Option<i32>, notes: &[Note]) -> i32 {
+    line_start
+        .take()
+        .or_else(|| notes.iter().find_map(|note| note.start()))
+        .unwrap_or(0)
+}
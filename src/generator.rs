@@ -1,121 +1,479 @@
+use crate::duet::DuetSong;
 use crate::structs::*;
+use std::io::Write;
 
 error_chain! {
     errors {
+        #[doc="a tag can not be written in the requested version"]
+        UnsupportedTag(tag: &'static str) {
+            description("tag not supported by version")
+            display("{} tag is not supported by the requested version", tag)
+        }
         #[doc="the path encoding is invalid"]
         InvalidPathEncoding(tag: &'static str) {
             description("invalid path encoding")
             display("invalid path encoding on tag: {}", tag)
         }
+        #[doc="the song contains characters the output encoding can not represent"]
+        EncodingError(text: String) {
+            description("encoding error")
+            display("could not encode: {}", text)
+        }
+        #[doc="the output encoding can not be written in the requested version"]
+        UnsupportedEncoding {
+            description("encoding not supported by version")
+            display("only UTF-8 is supported by the requested version")
+        }
+        #[doc="input output error while writing the song"]
+        IOError {
+            description("io error")
+        }
     }
 }
 
-/// Converts a Song back to the Ultrastar Song format and returns it as a String
-///
-/// # Arguments
-/// * header - the Header struct of the song
-/// * lines - a vector of the songs lines
-///
-pub fn generate_song_txt(header: &Header, lines: &[Line]) -> Result<String> {
-    // generate header
-    let mp3_str = match header.audio_path.to_str() {
-        Some(x) => x,
-        None => bail!(ErrorKind::InvalidPathEncoding("MP3")),
+/// Describes the line ending the generator writes
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LineEnding {
+    /// unix line endings (\n)
+    Lf,
+    /// windows line endings (\r\n)
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Describes the encoding the generator writes
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum OutputEncoding {
+    /// UTF-8, the encoding of every versioned file
+    Utf8,
+    /// Windows-1252, the encoding of many legacy files
+    #[cfg(feature = "encoding-support")]
+    Windows1252,
+}
+
+impl OutputEncoding {
+    // returns the value of the #ENCODING tag that names the encoding
+    fn tag_value(self) -> &'static str {
+        match self {
+            OutputEncoding::Utf8 => "UTF8",
+            #[cfg(feature = "encoding-support")]
+            OutputEncoding::Windows1252 => "CP1252",
+        }
+    }
+}
+
+/// Describes how the generator formats a song
+#[derive(PartialEq, Clone, Debug)]
+pub struct GeneratorOptions {
+    /// the version of the file format to write, None uses the version of the header
+    pub version: Option<Version>,
+    /// the line ending to write
+    pub line_ending: LineEnding,
+    /// the encoding to write, versioned files can only be written in UTF-8
+    pub encoding: OutputEncoding,
+    /// write a byte order mark, this only applies to UTF-8
+    pub bom: bool,
+    /// the tags to write first, in this order, the other tags follow in the default order
+    pub tag_order: Vec<String>,
+    /// write YES/NO and ON/OFF values in lower case
+    pub lowercase_switches: bool,
+    /// the number of decimal places of floats like BPM and GAP, None writes as few as needed
+    pub float_precision: Option<usize>,
+    /// write floats with a decimal comma instead of a decimal point
+    pub decimal_comma: bool,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            version: None,
+            line_ending: LineEnding::Lf,
+            encoding: OutputEncoding::Utf8,
+            bom: false,
+            tag_order: Vec::new(),
+            lowercase_switches: false,
+            float_precision: None,
+            decimal_comma: false,
+        }
+    }
+}
+
+impl GeneratorOptions {
+    fn format_float(&self, value: f32) -> String {
+        let float_str = match self.float_precision {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => value.to_string(),
+        };
+        if self.decimal_comma {
+            float_str.replace('.', ",")
+        } else {
+            float_str
+        }
+    }
+
+    fn format_switch(&self, value: bool, on: &str, off: &str) -> String {
+        let switch = if value { on } else { off };
+        if self.lowercase_switches {
+            switch.to_lowercase()
+        } else {
+            String::from(switch)
+        }
+    }
+}
+
+// encodes and writes the song line by line
+struct SongWriter<'a, W: Write> {
+    writer: &'a mut W,
+    options: &'a GeneratorOptions,
+}
+
+impl<'a, W: Write> SongWriter<'a, W> {
+    fn write_str(&mut self, text: &str) -> Result<()> {
+        match self.options.encoding {
+            OutputEncoding::Utf8 => self
+                .writer
+                .write_all(text.as_bytes())
+                .chain_err(|| ErrorKind::IOError),
+            #[cfg(feature = "encoding-support")]
+            OutputEncoding::Windows1252 => {
+                use encoding::Encoding;
+                let bytes =
+                    match encoding::all::WINDOWS_1252.encode(text, encoding::EncoderTrap::Strict) {
+                        Ok(x) => x,
+                        Err(e) => bail!(ErrorKind::EncodingError(e.into_owned())),
+                    };
+                self.writer
+                    .write_all(&bytes)
+                    .chain_err(|| ErrorKind::IOError)
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        self.write_str(line)?;
+        self.write_str(self.options.line_ending.as_str())
+    }
+}
+
+fn path_str(tag: &'static str, path: &Source) -> Result<String> {
+    match path.to_str() {
+        Some(x) => Ok(String::from(x)),
+        None => bail!(ErrorKind::InvalidPathEncoding(tag)),
+    }
+}
+
+// collects the tags of the header in the default order
+fn header_tags(
+    header: &Header,
+    version: Version,
+    options: &GeneratorOptions,
+) -> Result<Vec<(String, String)>> {
+    let mut tags: Vec<(&str, String)> = Vec::new();
+    if let Some(version_str) = version.to_str() {
+        tags.push(("VERSION", String::from(version_str)));
+    }
+    let audio_tag = if version.uses_audio_tag() {
+        "AUDIO"
+    } else {
+        "MP3"
     };
-    let mut song_txt_str = format!(
-        "#TITLE:{}\n#ARTIST:{}\n#MP3:{}\n#BPM:{}\n",
-        header.title, header.artist, mp3_str, header.bpm
-    );
+    tags.push(("TITLE", header.title.clone()));
+    tags.push(("ARTIST", header.artist.clone()));
+    tags.push((audio_tag, path_str(audio_tag, &header.audio_path)?));
+    // the #MP3 fallback of a versioned file is kept as unknown tag and written next to #AUDIO
+    let mp3_fallback = header
+        .unknown
+        .as_ref()
+        .and_then(|unknown| unknown.get("MP3"));
+    if let Some(mp3) = mp3_fallback.filter(|_| version.uses_audio_tag()) {
+        tags.push(("MP3", mp3.clone()));
+    }
+    tags.push(("BPM", options.format_float(header.bpm)));
     if let Some(gap) = header.gap {
-        song_txt_str.push_str(&format!("#GAP:{}\n", gap));
+        tags.push(("GAP", options.format_float(gap)));
     }
-    if let Some(cover_path) = header.cover_path.clone() {
-        let cover_str = match cover_path.to_str() {
-            Some(x) => x,
-            None => bail!(ErrorKind::InvalidPathEncoding("COVER")),
-        };
-        song_txt_str.push_str(&format!("#COVER:{}\n", cover_str));
+    if let Some(ref cover_path) = header.cover_path {
+        tags.push(("COVER", path_str("COVER", cover_path)?));
     }
-    if let Some(background_path) = header.background_path.clone() {
-        let background_str = match background_path.to_str() {
-            Some(x) => x,
-            None => bail!(ErrorKind::InvalidPathEncoding("BACKGROUND")),
-        };
-        song_txt_str.push_str(&format!("#BACKGROUND:{}\n", background_str));
+    if let Some(ref background_path) = header.background_path {
+        tags.push(("BACKGROUND", path_str("BACKGROUND", background_path)?));
     }
-    if let Some(video_path) = header.video_path.clone() {
-        let video_str = match video_path.to_str() {
-            Some(x) => x,
-            None => bail!(ErrorKind::InvalidPathEncoding("VIDEO")),
-        };
-        song_txt_str.push_str(&format!("#VIDEO:{}\n", video_str));
+    if let Some(ref video_path) = header.video_path {
+        tags.push(("VIDEO", path_str("VIDEO", video_path)?));
     }
     if let Some(videogap) = header.video_gap {
-        song_txt_str.push_str(&format!("#VIDEOGAP:{}\n", videogap));
+        tags.push(("VIDEOGAP", options.format_float(videogap)));
     }
-    if let Some(genre) = header.genre.clone() {
-        song_txt_str.push_str(&format!("#GENRE:{}\n", genre));
+    if let Some(ref genre) = header.genre {
+        tags.push(("GENRE", genre.clone()));
     }
-    if let Some(edition) = header.edition.clone() {
-        song_txt_str.push_str(&format!("#EDITION:{}\n", edition));
+    if let Some(ref edition) = header.edition {
+        tags.push(("EDITION", edition.clone()));
     }
-    if let Some(language) = header.language.clone() {
-        song_txt_str.push_str(&format!("#LANGUAGE:{}\n", language));
+    if let Some(ref language) = header.language {
+        tags.push(("LANGUAGE", language.clone()));
     }
     if let Some(year) = header.year {
-        song_txt_str.push_str(&format!("#YEAR:{}\n", year));
+        tags.push(("YEAR", year.to_string()));
+    }
+    if let Some(ref creator) = header.creator {
+        tags.push(("CREATOR", creator.clone()));
+    }
+    if let Some(start) = header.start {
+        tags.push(("START", options.format_float(start)));
+    }
+    if let Some(end) = header.end {
+        tags.push(("END", end.to_string()));
+    }
+    // the editor settings and the encoding are dropped by versions that do not know them
+    if let Some(resolution) = header.resolution {
+        if version.allows_tag("RESOLUTION") {
+            tags.push(("RESOLUTION", resolution.to_string()));
+        }
+    }
+    if let Some(notes_gap) = header.notes_gap {
+        if version.allows_tag("NOTESGAP") {
+            tags.push(("NOTESGAP", notes_gap.to_string()));
+        }
+    }
+    // the tag names the encoding that is written, it is required for other encodings than UTF-8
+    let encoding_needed = header.encoding.is_some() || options.encoding != OutputEncoding::Utf8;
+    if encoding_needed && version.allows_tag("ENCODING") {
+        tags.push(("ENCODING", String::from(options.encoding.tag_value())));
+    }
+    if let Some(preview_start) = header.preview_start {
+        tags.push(("PREVIEWSTART", options.format_float(preview_start)));
+    }
+    if let Some(medley_start_beat) = header.medley_start_beat {
+        tags.push(("MEDLEYSTARTBEAT", medley_start_beat.to_string()));
+    }
+    if let Some(medley_end_beat) = header.medley_end_beat {
+        tags.push(("MEDLEYENDBEAT", medley_end_beat.to_string()));
+    }
+    if let Some(calc_medley) = header.calc_medley {
+        tags.push((
+            "CALCMEDLEY",
+            options.format_switch(calc_medley, "ON", "OFF"),
+        ));
+    }
+    let (p1_tag, p2_tag) = if version.uses_short_duet_tags() {
+        ("P1", "P2")
+    } else {
+        ("DUETSINGERP1", "DUETSINGERP2")
+    };
+    if let Some(ref duet_singer_p1) = header.duet_singer_p1 {
+        tags.push((p1_tag, duet_singer_p1.clone()));
+    }
+    if let Some(ref duet_singer_p2) = header.duet_singer_p2 {
+        tags.push((p2_tag, duet_singer_p2.clone()));
     }
     if let Some(relative) = header.relative {
-        if relative {
-            song_txt_str.push_str("#RELATIVE:YES\n");
+        if !version.allows_tag("RELATIVE") {
+            // absolute timing is the only timing of this version
+            if relative {
+                bail!(ErrorKind::UnsupportedTag("RELATIVE"));
+            }
         } else {
-            song_txt_str.push_str("#RELATIVE:NO\n");
+            tags.push(("RELATIVE", options.format_switch(relative, "YES", "NO")));
         }
     }
-    if let Some(unknown) = header.unknown.clone() {
+    let mut tags: Vec<(String, String)> = tags
+        .into_iter()
+        .map(|(tag, value)| (String::from(tag), value))
+        .collect();
+    if let Some(ref unknown) = header.unknown {
         for (key, value) in unknown.iter() {
-            song_txt_str.push_str(&format!("#{}:{}\n", key, value));
+            // a legacy #AUDIO tag would duplicate the audio path, the #MP3 fallback is written above
+            if key == audio_tag || key == "MP3" {
+                continue;
+            }
+            tags.push((key.clone(), value.clone()));
         }
     }
+    Ok(tags)
+}
+
+pub(crate) fn note_line(note: &Note, options: &GeneratorOptions) -> String {
+    let note_type = match *note {
+        Note::Regular { .. } => ':',
+        Note::Golden { .. } => '*',
+        Note::Freestyle { .. } => 'F',
+        Note::Rap { .. } => 'R',
+        Note::RapGolden { .. } => 'G',
+        Note::PlayerChange { player } => return format!("P{}", player),
+        Note::BpmChange { beat, bpm } => {
+            return format!("B {} {}", beat, options.format_float(bpm))
+        }
+    };
+    // every other note has all of these values
+    format!(
+        "{} {} {} {} {}",
+        note_type,
+        note.start().unwrap(),
+        note.duration().unwrap(),
+        note.pitch().unwrap(),
+        note.text().unwrap()
+    )
+}
+
+/// Converts a Song back to the Ultrastar Song format and returns it as a String
+///
+/// The song is written in the version given by its header.
+///
+/// # Arguments
+/// * header - the Header struct of the song
+/// * lines - a vector of the songs lines
+///
+pub fn generate_song_txt(header: &Header, lines: &[Line]) -> Result<String> {
+    generate_song_txt_for_version(header, lines, header.version)
+}
+
+/// Converts a duet back to the Ultrastar Song format and returns it as a String
+///
+/// The tracks are written in the P1/P2 layout, see `DuetSong::to_song`.
+///
+/// # Arguments
+/// * duet - the duet with the tracks of its players
+///
+pub fn generate_duet_txt(duet: &DuetSong) -> Result<String> {
+    let song = duet.to_song();
+    generate_song_txt(&song.header, &song.lines)
+}
+
+/// Converts a Song to the given version of the Ultrastar Song format and returns it as a String
+///
+/// # Arguments
+/// * header - the Header struct of the song
+/// * lines - a vector of the songs lines
+/// * version - the version of the file format to write
+///
+pub fn generate_song_txt_for_version(
+    header: &Header,
+    lines: &[Line],
+    version: Version,
+) -> Result<String> {
+    let mut song_txt = Vec::new();
+    write_song_txt_for_version(&mut song_txt, header, lines, version)?;
+    // the song is written as UTF-8
+    Ok(String::from_utf8(song_txt).unwrap())
+}
+
+/// Converts a Song to the Ultrastar Song format as described by the options and returns its bytes
+///
+/// # Arguments
+/// * header - the Header struct of the song
+/// * lines - a vector of the songs lines
+/// * options - the options that describe how to format the song
+///
+pub fn generate_song_bytes(
+    header: &Header,
+    lines: &[Line],
+    options: &GeneratorOptions,
+) -> Result<Vec<u8>> {
+    let mut song_txt = Vec::new();
+    write_song_txt_with_options(&mut song_txt, header, lines, options)?;
+    Ok(song_txt)
+}
+
+/// Writes a Song in the Ultrastar Song format to the given writer
+///
+/// The song is written in the version given by its header.
+/// If an error occurs, the part of the song written so far is left in the writer.
+///
+/// # Arguments
+/// * writer - the writer to write the song to
+/// * header - the Header struct of the song
+/// * lines - a vector of the songs lines
+///
+pub fn write_song_txt<W: Write>(writer: &mut W, header: &Header, lines: &[Line]) -> Result<()> {
+    write_song_txt_with_options(writer, header, lines, &GeneratorOptions::default())
+}
+
+/// Writes a Song in the given version of the Ultrastar Song format to the given writer
+///
+/// If an error occurs, the part of the song written so far is left in the writer.
+///
+/// # Arguments
+/// * writer - the writer to write the song to
+/// * header - the Header struct of the song
+/// * lines - a vector of the songs lines
+/// * version - the version of the file format to write
+///
+pub fn write_song_txt_for_version<W: Write>(
+    writer: &mut W,
+    header: &Header,
+    lines: &[Line],
+    version: Version,
+) -> Result<()> {
+    let options = GeneratorOptions {
+        version: Some(version),
+        ..GeneratorOptions::default()
+    };
+    write_song_txt_with_options(writer, header, lines, &options)
+}
+
+/// Writes a Song in the Ultrastar Song format as described by the options to the given writer
+///
+/// Legacy files name the output encoding with an #ENCODING tag if it is not UTF-8 or the header
+/// has an encoding, versioned files fail if the output encoding is not UTF-8.
+/// If an error occurs, the part of the song written so far is left in the writer.
+///
+/// # Arguments
+/// * writer - the writer to write the song to
+/// * header - the Header struct of the song
+/// * lines - a vector of the songs lines
+/// * options - the options that describe how to format the song
+///
+pub fn write_song_txt_with_options<W: Write>(
+    writer: &mut W,
+    header: &Header,
+    lines: &[Line],
+    options: &GeneratorOptions,
+) -> Result<()> {
+    let version = options.version.unwrap_or(header.version);
+    if version >= Version::V1_0_0 && options.encoding != OutputEncoding::Utf8 {
+        bail!(ErrorKind::UnsupportedEncoding);
+    }
+    let mut tags = header_tags(header, version, options)?;
+    tags.sort_by_key(|(tag, _)| {
+        options
+            .tag_order
+            .iter()
+            .position(|x| x == tag)
+            .unwrap_or(options.tag_order.len())
+    });
+
+    let mut song_writer = SongWriter { writer, options };
+    if options.bom && options.encoding == OutputEncoding::Utf8 {
+        song_writer.write_str("\u{feff}")?;
+    }
+
+    // generate header
+    for (tag, value) in tags.iter() {
+        song_writer.write_line(&format!("#{}:{}", tag, value))?;
+    }
 
     // generate lines
-    for line in lines.iter() {
-        if line.start != 0 {
-            if line.rel.is_some() {
-                song_txt_str.push_str(format!("- {} {}\n", line.start, line.rel.unwrap()).as_ref());
+    for (i, line) in lines.iter().enumerate() {
+        // a break at beat 0 still separates the line from the previous one
+        if i > 0 || line.start != 0 || line.rel.is_some() {
+            if let Some(rel) = line.rel {
+                song_writer.write_line(&format!("- {} {}", line.start, rel))?;
             } else {
-                song_txt_str.push_str(format!("- {}\n", line.start).as_ref());
+                song_writer.write_line(&format!("- {}", line.start))?;
             }
         }
         for note in line.notes.iter() {
-            match *note {
-                Note::Regular {
-                    start,
-                    duration,
-                    pitch,
-                    ref text,
-                } => song_txt_str
-                    .push_str(format!(": {} {} {} {}\n", start, duration, pitch, text).as_ref()),
-                Note::Golden {
-                    start,
-                    duration,
-                    pitch,
-                    ref text,
-                } => song_txt_str
-                    .push_str(format!("* {} {} {} {}\n", start, duration, pitch, text).as_ref()),
-                Note::Freestyle {
-                    start,
-                    duration,
-                    pitch,
-                    ref text,
-                } => song_txt_str
-                    .push_str(format!("F {} {} {} {}\n", start, duration, pitch, text).as_ref()),
-                Note::PlayerChange { player } => {
-                    song_txt_str.push_str(format!("P{}\n", player).as_ref())
-                }
-            };
-        }
-    }
-    song_txt_str.push_str("E");
-    Ok(song_txt_str)
+            song_writer.write_line(&note_line(note, options))?;
+        }
+    }
+    song_writer.write_str("E")
 }
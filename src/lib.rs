@@ -1,6 +1,8 @@
 //! # Ultrastar TXT Library
 //! This is a small library that is able to parse and generate song files for the open source karaoke game Ultrastar.
 #![deny(missing_docs)]
+// every module brings its own error_chain types, they are meant to be used by their module path
+#![allow(ambiguous_glob_reexports)]
 
 #[macro_use]
 extern crate error_chain;
@@ -10,20 +12,53 @@ extern crate regex;
 #[cfg(feature = "url-support")]
 extern crate url;
 
+/// this module contains the automatic repair of songs
+pub mod autofix;
+/// this module contains the lossless document model
+pub mod document;
+/// this module contains the duet model
+pub mod duet;
 /// this module contains the generator
 pub mod generator;
+/// this module contains the conversion between songs and LRC lyrics
+pub mod lrc;
+/// this module contains the extraction of lyrics
+pub mod lyrics;
+/// this module contains the conversion between songs and MIDI files
+pub mod midi;
 /// this module contains the parser
 pub mod parser;
+/// this module contains the statistics of songs
+pub mod stats;
 /// this module contains the structs that represent the parsed data
 pub mod structs;
+/// this module contains the export of lyrics to subtitles
+pub mod subtitle;
+/// this module contains the conversion between beats and time
+pub mod timing;
+/// this module contains transformations of songs
+pub mod transform;
+/// this module contains the validation of songs
+pub mod validate;
 
 #[cfg(feature = "file-support")]
 /// this module contains functions to parse songs from a path
 pub mod loader;
 
+pub use crate::autofix::*;
+pub use crate::document::*;
+pub use crate::duet::*;
 pub use crate::generator::*;
+pub use crate::lrc::*;
+pub use crate::lyrics::*;
+pub use crate::midi::*;
 pub use crate::parser::*;
+pub use crate::stats::*;
 pub use crate::structs::*;
+pub use crate::subtitle::*;
+pub use crate::timing::*;
+pub use crate::transform::*;
+pub use crate::validate::*;
 
 #[cfg(feature = "file-support")]
 pub use crate::loader::*;
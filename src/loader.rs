@@ -1,7 +1,10 @@
+// the code error_chain generates for the errors matches on the deprecated errors
+#![allow(deprecated)]
+
 extern crate chardet;
 extern crate encoding;
 
-use crate::parser::{parse_txt_header_str, parse_txt_lines_str};
+use crate::parser::{parse_txt_song_str, parse_txt_song_str_lenient, Diagnostic};
 use crate::structs::{TXTSong, Source};
 use std::fs::File;
 use std::io::Read;
@@ -27,29 +30,29 @@ error_chain! {
             description("canonicalization error")
         }
         #[doc="error in parsing the song header"]
+        #[deprecated(note = "the header and the lines are parsed together, see SongParsingError")]
         HeaderParsingError {
             description("header parsing error")
         }
         #[doc="error in parsing the songs lines"]
+        #[deprecated(note = "the header and the lines are parsed together, see SongParsingError")]
         LinesParsingError {
             description("lines parsing error")
         }
+        #[doc="error in parsing the song"]
+        SongParsingError {
+            description("song parsing error")
+        }
     }
 }
 
-fn read_file_to_string<P: AsRef<Path>>(p: P) -> Result<String> {
-    let p = p.as_ref();
-    let mut f = File::open(p).chain_err(|| ErrorKind::IOError)?;
-    let mut reader: Vec<u8> = Vec::new();
-    f.read_to_end(&mut reader)
-        .chain_err(|| ErrorKind::IOError)?;
-
+fn decode_bytes(bytes: &[u8]) -> Result<String> {
     // detect encoding and decode to String
-    let chardet_result = chardet::detect(&reader);
+    let chardet_result = chardet::detect(bytes);
     let whtwg_label = chardet::charset2encoding(&chardet_result.0);
     let coder = encoding::label::encoding_from_whatwg_label(whtwg_label);
     let file_content = match coder {
-        Some(c) => match c.decode(&reader, encoding::DecoderTrap::Ignore) {
+        Some(c) => match c.decode(bytes, encoding::DecoderTrap::Ignore) {
             Ok(x) => x,
             Err(e) => bail!(ErrorKind::DecodingError(e.into_owned())),
         },
@@ -59,6 +62,19 @@ fn read_file_to_string<P: AsRef<Path>>(p: P) -> Result<String> {
     Ok(file_content)
 }
 
+fn read_to_string<R: Read>(mut reader: R) -> Result<String> {
+    let mut bytes: Vec<u8> = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .chain_err(|| ErrorKind::IOError)?;
+    decode_bytes(&bytes)
+}
+
+fn read_file_to_string<P: AsRef<Path>>(p: P) -> Result<String> {
+    let f = File::open(p.as_ref()).chain_err(|| ErrorKind::IOError)?;
+    read_to_string(f)
+}
+
 fn canonicalize_path<B: AsRef<Path>>(
     path: &Option<Source>,
     base_path: B,
@@ -90,10 +106,8 @@ pub fn parse_txt_song<P: AsRef<Path>>(path: P) -> Result<TXTSong> {
     let path = path.as_ref();
     let txt = read_file_to_string(path)?;
 
-    let mut txt_song = TXTSong {
-        header: parse_txt_header_str(txt.as_ref()).chain_err(|| ErrorKind::HeaderParsingError)?,
-        lines: parse_txt_lines_str(txt.as_ref()).chain_err(|| ErrorKind::LinesParsingError)?,
-    };
+    let mut txt_song =
+        parse_txt_song_str(txt.as_ref()).chain_err(|| ErrorKind::SongParsingError)?;
 
     // canonicalize paths
     if let Some(base_path) = path.parent() {
@@ -110,3 +124,29 @@ pub fn parse_txt_song<P: AsRef<Path>>(path: P) -> Result<TXTSong> {
 
     Ok(txt_song)
 }
+
+/// Reads a song from any reader and returns a TXTSong struct
+///
+/// The encoding of the song is detected the same way as for song files.
+/// As there is no path to the song, local sources are not canonicalized.
+///
+/// # Arguments
+/// * reader - the reader to read the song from, e.g. a file in an archive or stdin
+///
+pub fn parse_txt_song_reader<R: Read>(reader: R) -> Result<TXTSong> {
+    let txt = read_to_string(reader)?;
+    parse_txt_song_str(txt.as_ref()).chain_err(|| ErrorKind::SongParsingError)
+}
+
+/// Reads a song from any reader without giving up on the first problem in the song
+///
+/// See `parse_txt_song_str_lenient` for how problems are handled,
+/// only errors while reading and decoding the song are returned as Err.
+///
+/// # Arguments
+/// * reader - the reader to read the song from, e.g. a file in an archive or stdin
+///
+pub fn parse_txt_song_reader_lenient<R: Read>(reader: R) -> Result<(TXTSong, Vec<Diagnostic>)> {
+    let txt = read_to_string(reader)?;
+    Ok(parse_txt_song_str_lenient(txt.as_ref()))
+}
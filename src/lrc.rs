@@ -0,0 +1,332 @@
+use crate::lyrics::LyricLine;
+use crate::structs::{Header, Line, Note, TXTSong};
+use crate::timing::{beat_duration_ms, TimeConverter};
+use regex::Regex;
+
+error_chain! {
+    errors {
+        #[doc="a timestamp or tag could not be parsed"]
+        InvalidTimestamp(line: u32) {
+            description("invalid timestamp")
+            display("invalid timestamp in line: {}", line)
+        }
+        #[doc="the file contains no timed lyrics"]
+        MissingLyrics {
+            description("file contains no timed lyrics")
+        }
+        #[doc="the bpm to import the lyrics with are not a finite number greater than 0"]
+        InvalidBpm {
+            description("bpm must be a finite number greater than 0")
+        }
+    }
+}
+
+// the time a word is sung if the file does not tell, in ms
+const DEFAULT_WORD_DURATION: f32 = 500.0;
+
+// formats a time in ms as a timestamp of the form mm:ss.xx
+fn format_timestamp(ms: f32) -> String {
+    let centiseconds = (ms.max(0.0) / 10.0).round() as u64;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centiseconds / 6000,
+        centiseconds / 100 % 60,
+        centiseconds % 100
+    )
+}
+
+// writes the id tags of the song
+fn lrc_tags(song: &TXTSong) -> String {
+    let mut lrc = format!("[ar:{}]\n[ti:{}]\n", song.header.artist, song.header.title);
+    if let Some(ref creator) = song.header.creator {
+        lrc.push_str(&format!("[by:{}]\n", creator));
+    }
+    lrc
+}
+
+// writes a line of enhanced LRC with a timestamp in front of every word
+fn enhanced_line(converter: &TimeConverter, line: &LyricLine) -> String {
+    let mut text = String::new();
+    for (i, word) in line.words.iter().enumerate() {
+        if i > 0 {
+            text.push(' ');
+        }
+        let start = format_timestamp(converter.ms_at_beat(word.start as f32));
+        text.push_str(&format!("<{}>{}", start, word.text));
+    }
+    let end = format_timestamp(converter.ms_at_beat(line.end() as f32));
+    text.push_str(&format!("<{}>", end));
+    text
+}
+
+// writes the lyrics of the song as LRC
+fn generate_lrc_lines(song: &TXTSong, enhanced: bool) -> String {
+    let mut lrc = lrc_tags(song);
+    let converter = song.time_converter();
+    // the lines of a duet are interleaved by their start
+    let mut lyric_lines = song.lyric_lines();
+    lyric_lines.sort_by_key(LyricLine::start);
+    for line in lyric_lines.iter() {
+        let start = format_timestamp(converter.ms_at_beat(line.start() as f32));
+        let text = if enhanced {
+            enhanced_line(&converter, line)
+        } else {
+            line.text()
+        };
+        lrc.push_str(&format!("[{}]{}\n", start, text));
+    }
+    // an empty line removes the last line from the display when it is sung
+    if let Some(end) = lyric_lines.iter().map(LyricLine::end).max() {
+        let end = format_timestamp(converter.ms_at_beat(end as f32));
+        lrc.push_str(&format!("[{}]\n", end));
+    }
+    lrc
+}
+
+/// Converts the lyrics of a song to LRC with a timestamp for every line
+///
+/// The artist and title of the header are written as [ar:] and [ti:] tags,
+/// the creator as [by:] tag. The lines are sorted by their start, so the lines of a duet
+/// are written in the order they are sung.
+///
+/// # Arguments
+/// * song - the song to convert
+///
+pub fn generate_lrc(song: &TXTSong) -> String {
+    generate_lrc_lines(song, false)
+}
+
+/// Converts the lyrics of a song to enhanced LRC with a timestamp for every word
+///
+/// Every line starts with a line timestamp, every word is preceded by a word timestamp
+/// and the line ends with a word timestamp of its end.
+///
+/// # Arguments
+/// * song - the song to convert
+///
+pub fn generate_enhanced_lrc(song: &TXTSong) -> String {
+    generate_lrc_lines(song, true)
+}
+
+/// Describes how the lyrics of an LRC file are converted into notes
+#[derive(PartialEq, Clone, Debug)]
+pub struct LrcOptions {
+    /// the bpm of the created song
+    pub bpm: f32,
+    /// create freestyle notes instead of regular notes
+    pub freestyle: bool,
+    /// the pitch of the created notes
+    pub pitch: i32,
+}
+
+impl Default for LrcOptions {
+    fn default() -> Self {
+        LrcOptions {
+            bpm: 300.0,
+            freestyle: true,
+            pitch: 0,
+        }
+    }
+}
+
+// a word of an LRC file and the time it is sung at, if the file tells
+type LrcWord = (Option<f32>, String);
+
+// a line of the LRC file with the words and the times they are sung at
+struct LrcLine {
+    time: f32,
+    words: Vec<LrcWord>,
+    end: Option<f32>,
+}
+
+// converts minutes and seconds of a timestamp into ms
+fn parse_timestamp(minutes: &str, seconds: &str) -> Option<f32> {
+    let minutes: f32 = minutes.parse().ok()?;
+    let seconds: f32 = seconds.replace(':', ".").parse().ok()?;
+    Some((minutes * 60.0 + seconds) * 1000.0)
+}
+
+// splits the text of a line into words, enhanced LRC gives the time of every word
+fn parse_words(text: &str, line_count: u32) -> Result<(Vec<LrcWord>, Option<f32>)> {
+    lazy_static! {
+        static ref WORD_RE: Regex = Regex::new(r"<([0-9]+):([0-9]+(?:[.:][0-9]+)?)>").unwrap();
+    }
+    if !text.contains('<') {
+        let words = text
+            .split_whitespace()
+            .map(|word| (None, format!("{} ", word)))
+            .collect();
+        return Ok((words, None));
+    }
+    let mut words = Vec::new();
+    let mut end = None;
+    let stamps: Vec<_> = WORD_RE.captures_iter(text).collect();
+    for (i, cap) in stamps.iter().enumerate() {
+        let time = parse_timestamp(&cap[1], &cap[2])
+            .ok_or_else(|| Error::from(ErrorKind::InvalidTimestamp(line_count)))?;
+        let text_start = cap.get(0).unwrap().end();
+        let text_end = stamps
+            .get(i + 1)
+            .map_or(text.len(), |next| next.get(0).unwrap().start());
+        let word = text[text_start..text_end].trim_start();
+        if word.is_empty() {
+            end = Some(time);
+        } else {
+            words.push((Some(time), String::from(word)));
+        }
+    }
+    Ok((words, end))
+}
+
+/// Creates a draft song from the lyrics of an (enhanced) LRC file
+///
+/// Every word, or every syllable of enhanced LRC, becomes a note and every line of the file
+/// becomes a line of the song. The gap is set to the first word, the [ar:], [ti:] and [by:] tags
+/// are used as artist, title and creator and the [offset:] tag is applied.
+/// The audio path is set to `Header::default_audio_path` of the artist and title.
+/// Words without their own timestamp share the time until the next line evenly.
+///
+/// # Arguments
+/// * lrc_str - a &str that contains the LRC file
+/// * options - the bpm and the kind of the notes to create
+///
+pub fn parse_lrc_str(lrc_str: &str, options: &LrcOptions) -> Result<TXTSong> {
+    lazy_static! {
+        static ref TIME_RE: Regex = Regex::new(r"^\[([0-9]+):([0-9]+(?:[.:][0-9]+)?)\]").unwrap();
+        static ref TAG_RE: Regex = Regex::new(r"^\[([a-zA-Z]+):(.*)\]$").unwrap();
+    }
+    if !(options.bpm.is_finite() && options.bpm > 0.0) {
+        bail!(ErrorKind::InvalidBpm);
+    }
+    let mut artist = String::new();
+    let mut title = String::new();
+    let mut creator = None;
+    let mut offset: f32 = 0.0;
+    let mut lrc_lines = Vec::new();
+
+    for (line, line_count) in lrc_str.lines().zip(1..) {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if let Some(cap) = TAG_RE.captures(line) {
+            let value = cap[2].trim();
+            match cap[1].to_lowercase().as_ref() {
+                "ar" => artist = String::from(value),
+                "ti" => title = String::from(value),
+                "by" => creator = Some(String::from(value)),
+                "offset" => {
+                    offset = value
+                        .trim_start_matches('+')
+                        .parse()
+                        .chain_err(|| ErrorKind::InvalidTimestamp(line_count))?;
+                    // "nan" and "inf" are parsed as well
+                    if !offset.is_finite() {
+                        bail!(ErrorKind::InvalidTimestamp(line_count));
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        // a line may be repeated at several times
+        let mut times = Vec::new();
+        let mut text = line;
+        while let Some(cap) = TIME_RE.captures(text) {
+            let time = parse_timestamp(&cap[1], &cap[2])
+                .ok_or_else(|| Error::from(ErrorKind::InvalidTimestamp(line_count)))?;
+            times.push(time);
+            text = &text[cap.get(0).unwrap().end()..];
+        }
+        if times.is_empty() {
+            if line.starts_with('[') {
+                bail!(ErrorKind::InvalidTimestamp(line_count));
+            }
+            continue;
+        }
+        let (words, end) = parse_words(text, line_count)?;
+        for time in times {
+            // a positive offset shows the lyrics earlier
+            let shift = |x: f32| x - offset;
+            lrc_lines.push(LrcLine {
+                time: shift(time),
+                words: words
+                    .iter()
+                    .map(|(word_time, word)| (word_time.map(shift), word.clone()))
+                    .collect(),
+                end: end.map(shift),
+            });
+        }
+    }
+    lrc_lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let gap = match lrc_lines.iter().find(|line| !line.words.is_empty()) {
+        Some(line) => line.words[0].0.unwrap_or(line.time),
+        None => bail!(ErrorKind::MissingLyrics),
+    };
+    let beat_duration = beat_duration_ms(options.bpm);
+    let beat = |ms: f32| ((ms - gap) / beat_duration).round() as i32;
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (i, lrc_line) in lrc_lines.iter().enumerate() {
+        if lrc_line.words.is_empty() {
+            continue;
+        }
+        let words = &lrc_line.words;
+        let line_start = words[0].0.unwrap_or(lrc_line.time);
+        let line_end = lrc_line
+            .end
+            .or_else(|| lrc_lines.get(i + 1).map(|next| next.time))
+            .unwrap_or(line_start + DEFAULT_WORD_DURATION * words.len() as f32);
+        let mut notes = Vec::new();
+        for (j, (word_time, word)) in words.iter().enumerate() {
+            let start = word_time
+                .unwrap_or(line_start + (line_end - line_start) * j as f32 / words.len() as f32);
+            let end = match words.get(j + 1) {
+                Some(&(Some(next_time), _)) => next_time,
+                Some(&(None, _)) => {
+                    line_start + (line_end - line_start) * (j + 1) as f32 / words.len() as f32
+                }
+                None => line_end,
+            };
+            let start = beat(start);
+            let duration = (beat(end) - start).max(1);
+            let text = if j + 1 == words.len() {
+                String::from(word.trim_end())
+            } else {
+                word.clone()
+            };
+            notes.push(if options.freestyle {
+                Note::Freestyle {
+                    start,
+                    duration,
+                    pitch: options.pitch,
+                    text,
+                }
+            } else {
+                Note::Regular {
+                    start,
+                    duration,
+                    pitch: options.pitch,
+                    text,
+                }
+            });
+        }
+        // the line break is placed at the end of the previous line
+        let line_break = match lines.last().and_then(|line| line.notes.last()) {
+            Some(note) => {
+                let previous_end = note.start().unwrap() + note.duration().unwrap();
+                previous_end.min(notes[0].start().unwrap())
+            }
+            None => 0,
+        };
+        lines.push(Line {
+            start: line_break,
+            rel: None,
+            notes,
+        });
+    }
+
+    let audio_path = Header::default_audio_path(&artist, &title);
+    let mut header = Header::new(&artist, &title, options.bpm, audio_path);
+    header.gap = Some(gap);
+    header.creator = creator;
+    Ok(TXTSong { header, lines })
+}
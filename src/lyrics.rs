@@ -0,0 +1,160 @@
+use crate::duet::Player;
+use crate::structs::{Note, TXTSong};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Describes a word of the lyrics, made from one or more syllables
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Debug)]
+pub struct LyricWord {
+    /// the text of the word without whitespace and continuation markers
+    pub text: String,
+    /// the absolute beat the first syllable of the word starts at
+    pub start: i32,
+    /// the absolute beat the last syllable or continuation of the word ends at
+    pub end: i32,
+}
+
+/// Describes a line of the lyrics
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Debug)]
+pub struct LyricLine {
+    /// the index of the line in the lines of the song
+    pub line: usize,
+    /// the player that sings the line, the first player if the song is not a duet
+    pub player: Player,
+    /// the words of the line
+    pub words: Vec<LyricWord>,
+}
+
+impl LyricLine {
+    /// returns the words of the line separated by spaces
+    pub fn text(&self) -> String {
+        let words: Vec<&str> = self.words.iter().map(|word| word.text.as_ref()).collect();
+        words.join(" ")
+    }
+
+    /// returns the absolute beat the line starts at
+    pub fn start(&self) -> i32 {
+        self.words.first().map_or(0, |word| word.start)
+    }
+
+    /// returns the absolute beat the line ends at
+    pub fn end(&self) -> i32 {
+        self.words.last().map_or(0, |word| word.end)
+    }
+}
+
+// joins the syllables of a line into words
+fn join_syllables(syllables: &[(&str, i32, i32)]) -> Vec<LyricWord> {
+    let mut words = Vec::new();
+    let mut current: Option<LyricWord> = None;
+    for &(text, start, end) in syllables.iter() {
+        // "~" marks a held syllable, some files use "-" instead
+        let syllable = text.trim().replace('~', "");
+        if syllable.is_empty() || syllable == "-" {
+            if let Some(ref mut word) = current {
+                word.end = end;
+            }
+            if text.ends_with(char::is_whitespace) {
+                words.extend(current.take());
+            }
+            continue;
+        }
+        // whitespace in front of or after a syllable separates words
+        if text.starts_with(char::is_whitespace) {
+            words.extend(current.take());
+        }
+        match current {
+            Some(ref mut word) => {
+                word.text.push_str(&syllable);
+                word.end = end;
+            }
+            None => {
+                current = Some(LyricWord {
+                    text: syllable,
+                    start,
+                    end,
+                })
+            }
+        }
+        if text.ends_with(char::is_whitespace) {
+            words.extend(current.take());
+        }
+    }
+    words.extend(current);
+    words
+}
+
+// the syllables of a line sung by one player, with their text and absolute start and end beats
+pub(crate) struct LinePart<'a> {
+    pub(crate) line: usize,
+    pub(crate) player: Player,
+    pub(crate) syllables: Vec<(&'a str, i32, i32)>,
+}
+
+impl TXTSong {
+    // splits the lines of the song at player changes, parts without syllables are left out
+    pub(crate) fn line_parts(&self) -> Vec<LinePart<'_>> {
+        let mut parts = Vec::new();
+        let mut player = Player::One;
+        for (line_index, line) in self.lines.iter().enumerate() {
+            let offset = self.line_offset(line_index);
+            let mut part = LinePart {
+                line: line_index,
+                player,
+                syllables: Vec::new(),
+            };
+            for note in line.notes.iter() {
+                if let Note::PlayerChange { player: number } = *note {
+                    player = Player::from_number(number).unwrap_or(player);
+                    let next_part = LinePart {
+                        line: line_index,
+                        player,
+                        syllables: Vec::new(),
+                    };
+                    parts.push(std::mem::replace(&mut part, next_part));
+                } else if let (Some(text), Some(start), Some(duration)) =
+                    (note.text(), note.start(), note.duration())
+                {
+                    let start = offset + start;
+                    part.syllables.push((text, start, start + duration));
+                }
+            }
+            parts.push(part);
+        }
+        parts.retain(|part| !part.syllables.is_empty());
+        parts
+    }
+
+    /// Returns the lyrics of the song line by line
+    ///
+    /// The syllables are joined into words, continuations are merged into the word they belong to.
+    /// Lines without text are left out. The lines of a duet are returned in the order of the file,
+    /// a line that contains a player change is split into a line for every player.
+    pub fn lyric_lines(&self) -> Vec<LyricLine> {
+        self.line_parts()
+            .iter()
+            .map(|part| LyricLine {
+                line: part.line,
+                player: part.player,
+                words: join_syllables(&part.syllables),
+            })
+            .filter(|line| !line.words.is_empty())
+            .collect()
+    }
+
+    /// returns all words of the lyrics in the order they are sung
+    pub fn lyric_words(&self) -> Vec<LyricWord> {
+        self.lyric_lines()
+            .into_iter()
+            .flat_map(|line| line.words)
+            .collect()
+    }
+
+    /// returns the lyrics of the song as plain text with one line of the song per line
+    pub fn lyrics(&self) -> String {
+        let lines: Vec<String> = self.lyric_lines().iter().map(LyricLine::text).collect();
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,573 @@
+use crate::structs::{Header, Line, Note, TXTSong};
+use crate::timing::beat_duration_ms;
+
+error_chain! {
+    errors {
+        #[doc="the file is not a valid Standard MIDI File"]
+        InvalidMidi(reason: &'static str) {
+            description("invalid MIDI file")
+            display("invalid MIDI file: {}", reason)
+        }
+        #[doc="the file is timed in SMPTE frames instead of ticks per quarter note"]
+        UnsupportedTiming {
+            description("SMPTE timing is not supported")
+        }
+        #[doc="the chosen track does not exist or contains no notes"]
+        TrackNotFound(track: usize) {
+            description("track not found")
+            display("track {} does not exist or contains no notes", track)
+        }
+        #[doc="the file contains no notes"]
+        MissingNotes {
+            description("file contains no notes")
+        }
+        #[doc="the bpm to import the notes with are not a finite number greater than 0"]
+        InvalidBpm {
+            description("bpm must be a finite number greater than 0")
+        }
+    }
+}
+
+/// the resolution of the exported MIDI files in ticks per quarter note
+pub const MIDI_TICKS_PER_QUARTER: u16 = 480;
+/// the number of MIDI ticks of a beat of a song, four beats of a song are a quarter note
+pub const MIDI_TICKS_PER_BEAT: u32 = 120;
+/// the offset between the pitch of a note and its MIDI note number, pitch 0 is C2
+pub const MIDI_PITCH_OFFSET: i32 = 48;
+
+// an event of a track at an absolute tick, events on the same tick are ordered by their rank
+struct TrackEvent {
+    tick: u32,
+    rank: u8,
+    data: Vec<u8>,
+}
+
+// appends a number as variable length quantity
+fn push_variable_length(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        groups.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+// creates a meta event with the given type and data
+fn meta_event(meta_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut event = vec![0xff, meta_type];
+    push_variable_length(&mut event, data.len() as u32);
+    event.extend_from_slice(data);
+    event
+}
+
+// creates a track chunk from its events
+fn track_chunk(mut events: Vec<TrackEvent>) -> Vec<u8> {
+    events.sort_by_key(|event| (event.tick, event.rank));
+    let mut data = Vec::new();
+    let mut tick = 0;
+    for event in events.iter() {
+        push_variable_length(&mut data, event.tick - tick);
+        data.extend_from_slice(&event.data);
+        tick = event.tick;
+    }
+    data.extend_from_slice(&[0x00, 0xff, 0x2f, 0x00]);
+
+    let mut chunk = b"MTrk".to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend(data);
+    chunk
+}
+
+// converts an absolute beat of the song into a tick
+fn tick_at_beat(gap_ticks: u32, beat: i32) -> u32 {
+    (gap_ticks as i64 + i64::from(beat) * i64::from(MIDI_TICKS_PER_BEAT)).max(0) as u32
+}
+
+// creates the track with the tempo changes of the song
+fn tempo_track(song: &TXTSong, gap_ticks: u32) -> Vec<u8> {
+    let mut events = vec![TrackEvent {
+        tick: 0,
+        rank: 0,
+        data: meta_event(0x03, song.header.title.as_bytes()),
+    }];
+    for (i, (beat, bpm)) in song.tempo_map().into_iter().enumerate() {
+        let microseconds = (60_000_000.0 / bpm).round() as u32;
+        events.push(TrackEvent {
+            // the bpm of the header is valid from the beginning of the audio
+            tick: if i == 0 {
+                0
+            } else {
+                tick_at_beat(gap_ticks, beat)
+            },
+            rank: 1,
+            data: meta_event(0x51, &microseconds.to_be_bytes()[1..]),
+        });
+    }
+    track_chunk(events)
+}
+
+// creates the track of a player from its lines with absolute timing
+fn vocal_track(name: &str, channel: u8, lines: &[Line], gap_ticks: u32) -> Vec<u8> {
+    let mut events = vec![TrackEvent {
+        tick: 0,
+        rank: 0,
+        data: meta_event(0x03, name.as_bytes()),
+    }];
+    for (i, line) in lines.iter().enumerate() {
+        // like in karaoke files every line but the first starts with a "/"
+        let mut line_break = i > 0;
+        for note in line.notes.iter() {
+            let (start, duration, pitch, text, velocity) = match *note {
+                Note::Regular {
+                    start,
+                    duration,
+                    pitch,
+                    ref text,
+                } => (start, duration, pitch, text, 100),
+                Note::Golden {
+                    start,
+                    duration,
+                    pitch,
+                    ref text,
+                } => (start, duration, pitch, text, 127),
+                Note::Freestyle {
+                    start,
+                    duration,
+                    pitch,
+                    ref text,
+                } => (start, duration, pitch, text, 64),
+                _ => continue,
+            };
+            let key = (pitch + MIDI_PITCH_OFFSET).clamp(0, 127) as u8;
+            let end = tick_at_beat(gap_ticks, start + duration);
+            let start = tick_at_beat(gap_ticks, start);
+            // notes without duration still need their note off after the note on
+            let end = end.max(start + 1);
+            // note offs come first, so notes that touch do not cut each other off
+            events.push(TrackEvent {
+                tick: end,
+                rank: 0,
+                data: vec![0x80 | channel, key, 0],
+            });
+            let text = if line_break {
+                format!("/{}", text)
+            } else {
+                text.clone()
+            };
+            line_break = false;
+            events.push(TrackEvent {
+                tick: start,
+                rank: 1,
+                data: meta_event(0x05, text.as_bytes()),
+            });
+            events.push(TrackEvent {
+                tick: start,
+                rank: 2,
+                data: vec![0x90 | channel, key, velocity],
+            });
+        }
+    }
+    track_chunk(events)
+}
+
+/// Converts the melody of a song to a Standard MIDI File
+///
+/// Regular, golden and freestyle notes become MIDI notes at their pitch plus 48 and
+/// their syllables become lyric events. Golden notes have the highest velocity, freestyle
+/// notes the lowest. The first track contains the tempo of the song, the gap is kept in front
+/// of the first note. Every player of a duet gets a track of its own, on a channel of its own.
+/// Like in karaoke files the first syllable of every line but the first starts with a "/".
+///
+/// # Arguments
+/// * song - the song to convert
+///
+pub fn generate_midi(song: &TXTSong) -> Vec<u8> {
+    let song = song.to_absolute();
+    // the gap is played at the bpm of the header, a quarter note lasts 60000 / bpm ms
+    let quarters = song.gap_ms() * song.header.bpm / 60_000.0;
+    let gap_ticks = (quarters * f32::from(MIDI_TICKS_PER_QUARTER))
+        .round()
+        .max(0.0) as u32;
+
+    let mut tracks = vec![tempo_track(&song, gap_ticks)];
+    match song.to_duet() {
+        Some(duet) => {
+            for (channel, track) in [&duet.p1, &duet.p2, &duet.both].iter().enumerate() {
+                if track.lines.is_empty() {
+                    continue;
+                }
+                let name = track
+                    .singer
+                    .clone()
+                    .unwrap_or_else(|| format!("P{}", track.player.number()));
+                tracks.push(vocal_track(&name, channel as u8, &track.lines, gap_ticks));
+            }
+        }
+        None => tracks.push(vocal_track("Vocals", 0, &song.lines, gap_ticks)),
+    }
+
+    let mut midi = b"MThd".to_vec();
+    midi.extend_from_slice(&6u32.to_be_bytes());
+    midi.extend_from_slice(&1u16.to_be_bytes());
+    midi.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    midi.extend_from_slice(&MIDI_TICKS_PER_QUARTER.to_be_bytes());
+    for track in tracks {
+        midi.extend(track);
+    }
+    midi
+}
+
+/// Describes how the notes of a MIDI file are converted into a song
+#[derive(PartialEq, Clone, Debug)]
+pub struct MidiOptions {
+    /// the bpm of the created song, the notes are quantised to its beats
+    pub bpm: f32,
+    /// the index of the track with the melody, the first track with notes and lyrics if not set
+    pub track: Option<usize>,
+}
+
+impl Default for MidiOptions {
+    fn default() -> Self {
+        MidiOptions {
+            bpm: 300.0,
+            track: None,
+        }
+    }
+}
+
+// a note of a track with its start and end tick
+struct MidiNote {
+    start: u32,
+    end: u32,
+    key: u8,
+}
+
+// the events of a track that are needed to create a song
+#[derive(Default)]
+struct MidiTrack {
+    name: Option<String>,
+    notes: Vec<MidiNote>,
+    lyrics: Vec<(u32, String)>,
+    texts: Vec<(u32, String)>,
+}
+
+impl MidiTrack {
+    // returns the syllables of the track, karaoke files use text events instead of lyric events
+    fn syllables(&self) -> Vec<(u32, String)> {
+        if !self.lyrics.is_empty() {
+            return self.lyrics.clone();
+        }
+        // text events starting with "@" contain information about the song
+        self.texts
+            .iter()
+            .filter(|(_, text)| !text.starts_with('@'))
+            .cloned()
+            .collect()
+    }
+}
+
+// reads the bytes of a MIDI file one after another
+struct MidiReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> MidiReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        MidiReader { bytes, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn read_slice(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self.position + length;
+        if end > self.bytes.len() {
+            bail!(ErrorKind::InvalidMidi("unexpected end of file"));
+        }
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn peek_u8(&self) -> Result<u8> {
+        match self.bytes.get(self.position) {
+            Some(&byte) => Ok(byte),
+            None => bail!(ErrorKind::InvalidMidi("unexpected end of file")),
+        }
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        let byte = self.peek_u8()?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let bytes = self.read_slice(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_variable_length(&mut self) -> Result<u32> {
+        let mut value = 0;
+        // a variable length quantity has at most four bytes
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!(ErrorKind::InvalidMidi(
+            "variable length quantity is too long"
+        ))
+    }
+}
+
+// decodes the text of a meta event, karaoke files are often encoded in Latin-1
+fn decode_text(data: &[u8]) -> String {
+    match std::str::from_utf8(data) {
+        Ok(text) => String::from(text),
+        Err(_) => data.iter().map(|&byte| char::from(byte)).collect(),
+    }
+}
+
+// reads the events of a track chunk, tempo changes are collected for all tracks
+fn read_track(data: &[u8], tempos: &mut Vec<(u32, u32)>) -> Result<MidiTrack> {
+    let mut reader = MidiReader::new(data);
+    let mut track = MidiTrack::default();
+    let mut tick = 0u32;
+    let mut running_status = None;
+    // notes that are on, with their channel, key and start tick
+    let mut open_notes: Vec<(u8, u8, u32)> = Vec::new();
+    while !reader.is_empty() {
+        tick = tick.saturating_add(reader.read_variable_length()?);
+        let status = if reader.peek_u8()? & 0x80 != 0 {
+            reader.read_u8()?
+        } else {
+            running_status.ok_or_else(|| Error::from(ErrorKind::InvalidMidi("missing status")))?
+        };
+        match status {
+            0xff => {
+                let meta_type = reader.read_u8()?;
+                let length = reader.read_variable_length()? as usize;
+                let data = reader.read_slice(length)?;
+                match meta_type {
+                    0x01 => track.texts.push((tick, decode_text(data))),
+                    0x03 if track.name.is_none() => track.name = Some(decode_text(data)),
+                    0x05 => track.lyrics.push((tick, decode_text(data))),
+                    0x51 if length == 3 => {
+                        tempos.push((tick, u32::from_be_bytes([0, data[0], data[1], data[2]])))
+                    }
+                    0x2f => break,
+                    _ => {}
+                }
+            }
+            0xf0 | 0xf7 => {
+                let length = reader.read_variable_length()? as usize;
+                reader.read_slice(length)?;
+            }
+            0x80..=0xef => {
+                running_status = Some(status);
+                let channel = status & 0x0f;
+                let data = match status & 0xf0 {
+                    0xc0 | 0xd0 => reader.read_slice(1)?,
+                    _ => reader.read_slice(2)?,
+                };
+                match status & 0xf0 {
+                    // a note on without velocity is a note off
+                    0x90 if data[1] > 0 => open_notes.push((channel, data[0], tick)),
+                    0x80 | 0x90 => {
+                        let position = open_notes
+                            .iter()
+                            .position(|&(c, key, _)| c == channel && key == data[0]);
+                        if let Some(position) = position {
+                            let (_, key, start) = open_notes.remove(position);
+                            track.notes.push(MidiNote {
+                                start,
+                                end: tick,
+                                key,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => bail!(ErrorKind::InvalidMidi("unknown event")),
+        }
+    }
+    track.notes.sort_by_key(|note| note.start);
+    Ok(track)
+}
+
+// converts a tick into ms from the beginning of the file
+fn ms_at_tick(tempos: &[(u32, u32)], ticks_per_quarter: u16, tick: u32) -> f32 {
+    // the time is summed up in microseconds to avoid rounding errors
+    let microseconds_of =
+        |ticks: u32, tempo: u32| u64::from(ticks) * u64::from(tempo) / u64::from(ticks_per_quarter);
+    let mut microseconds = 0;
+    let mut last_tick = 0;
+    // without tempo events a quarter note lasts half a second
+    let mut tempo = 500_000;
+    for &(change_tick, change_tempo) in tempos.iter() {
+        if change_tick >= tick {
+            break;
+        }
+        microseconds += microseconds_of(change_tick - last_tick, tempo);
+        last_tick = change_tick;
+        tempo = change_tempo;
+    }
+    microseconds += microseconds_of(tick - last_tick, tempo);
+    microseconds as f32 / 1000.0
+}
+
+// removes the line break markers of a syllable, returns if a line starts before or after it
+fn split_line_break(syllable: &str) -> (&str, bool, bool) {
+    let text = syllable.trim_start_matches(['/', '\\']);
+    let break_before = text.len() != syllable.len();
+    let trimmed = text.trim_end_matches(['\r', '\n']);
+    let break_after = trimmed.len() != text.len();
+    (trimmed, break_before, break_after)
+}
+
+/// Creates a draft song from a track of a MIDI or karaoke (.kar) file
+///
+/// Every note of the track becomes a note of the song at its MIDI note number minus 48,
+/// quantised to the beats of the chosen bpm. Lyric events, or the text events of karaoke files,
+/// become the syllables of the notes they are placed at, notes without a syllable are held with "~".
+/// A syllable starting with "/" or "\\" starts a new line, so does a syllable after a line end.
+/// The gap is set to the first note. The title and artist are taken from the "@T" text events
+/// of karaoke files, otherwise the name of the first track is used as title.
+/// The audio path is set to `Header::default_audio_path` of the artist and title.
+///
+/// # Arguments
+/// * midi - the bytes of the MIDI file
+/// * options - the bpm and the track to import
+///
+pub fn parse_midi(midi: &[u8], options: &MidiOptions) -> Result<TXTSong> {
+    if !(options.bpm.is_finite() && options.bpm > 0.0) {
+        bail!(ErrorKind::InvalidBpm);
+    }
+    let mut reader = MidiReader::new(midi);
+    if reader.read_slice(4)? != b"MThd" {
+        bail!(ErrorKind::InvalidMidi("missing header chunk"));
+    }
+    let header_length = reader.read_u32()? as usize;
+    if header_length < 6 {
+        bail!(ErrorKind::InvalidMidi("header chunk is too short"));
+    }
+    let header = reader.read_slice(header_length)?;
+    let ticks_per_quarter = u16::from_be_bytes([header[4], header[5]]);
+    if ticks_per_quarter & 0x8000 != 0 {
+        bail!(ErrorKind::UnsupportedTiming);
+    }
+    if ticks_per_quarter == 0 {
+        bail!(ErrorKind::InvalidMidi("division is zero"));
+    }
+
+    let mut tracks = Vec::new();
+    let mut tempos = Vec::new();
+    while !reader.is_empty() {
+        let chunk_type = reader.read_slice(4)?;
+        let length = reader.read_u32()? as usize;
+        let data = reader.read_slice(length)?;
+        // unknown chunks have to be ignored
+        if chunk_type == b"MTrk" {
+            tracks.push(read_track(data, &mut tempos)?);
+        }
+    }
+    tempos.sort_by_key(|&(tick, _)| tick);
+
+    let track = match options.track {
+        Some(index) => tracks
+            .get(index)
+            .filter(|track| !track.notes.is_empty())
+            .ok_or_else(|| Error::from(ErrorKind::TrackNotFound(index)))?,
+        None => tracks
+            .iter()
+            .find(|track| !track.notes.is_empty() && !track.syllables().is_empty())
+            .or_else(|| tracks.iter().find(|track| !track.notes.is_empty()))
+            .ok_or_else(|| Error::from(ErrorKind::MissingNotes))?,
+    };
+    // karaoke files often keep the lyrics in a track of their own
+    let mut syllables = track.syllables();
+    if syllables.is_empty() {
+        if let Some(lyrics_track) = tracks.iter().find(|track| !track.syllables().is_empty()) {
+            syllables = lyrics_track.syllables();
+        }
+    }
+
+    // the syllables are placed at the last note that starts at or before them
+    let notes = &track.notes;
+    let mut texts = vec![String::new(); notes.len()];
+    let mut line_breaks = vec![false; notes.len()];
+    for (tick, syllable) in syllables.iter() {
+        let index = notes
+            .partition_point(|note| note.start <= *tick)
+            .saturating_sub(1);
+        let (text, break_before, break_after) = split_line_break(syllable);
+        // a line break in front of the first syllable of a note is a break in front of the note
+        if break_before && texts[index].is_empty() {
+            line_breaks[index] = true;
+        }
+        texts[index].push_str(text);
+        if break_after && index + 1 < notes.len() {
+            line_breaks[index + 1] = true;
+        }
+    }
+
+    let ms = |tick: u32| ms_at_tick(&tempos, ticks_per_quarter, tick);
+    let gap = ms(notes[0].start);
+    let beat_duration = beat_duration_ms(options.bpm);
+    let beat = |tick: u32| ((ms(tick) - gap) / beat_duration).round() as i32;
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut previous_end = None;
+    for (i, (note, text)) in notes.iter().zip(texts).enumerate() {
+        let start = beat(note.start);
+        let duration = (beat(note.end) - start).max(1);
+        if i == 0 || line_breaks[i] {
+            // the line break is placed at the end of the previous line
+            lines.push(Line {
+                start: previous_end.map_or(0, |end: i32| end.min(start)),
+                rel: None,
+                notes: Vec::new(),
+            });
+        }
+        let text = if text.is_empty() {
+            String::from("~")
+        } else {
+            text
+        };
+        lines.last_mut().unwrap().notes.push(Note::Regular {
+            start,
+            duration,
+            pitch: i32::from(note.key) - MIDI_PITCH_OFFSET,
+            text,
+        });
+        previous_end = Some(start + duration);
+    }
+
+    // the first "@T" text event contains the title, the second the artist
+    let info: Vec<&str> = tracks
+        .iter()
+        .flat_map(|track| track.texts.iter())
+        .filter_map(|(_, text)| text.strip_prefix("@T"))
+        .map(str::trim)
+        .collect();
+    let title = match info.first() {
+        Some(title) => String::from(*title),
+        None => tracks
+            .first()
+            .and_then(|track| track.name.clone())
+            .unwrap_or_default(),
+    };
+    let artist = info.get(1).map_or("", |artist| artist);
+
+    let audio_path = Header::default_audio_path(artist, &title);
+    let mut header = Header::new(artist, &title, options.bpm, audio_path);
+    header.gap = Some(gap);
+    Ok(TXTSong { header, lines })
+}
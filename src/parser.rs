@@ -1,6 +1,7 @@
-use crate::structs::{Header, Line, Note, Source};
+use crate::structs::{Header, Line, Note, Severity, Source, TXTSong, Version};
 use regex::Regex;
 use std::collections::HashMap;
+use std::str::FromStr;
 
 error_chain! {
     errors {
@@ -19,6 +20,11 @@ error_chain! {
             description("could not parse value")
             display("could not parse {} in line: {}", field, line)
         }
+        #[doc="a header tag has no value"]
+        EmptyHeader(line: u32, tag: String) {
+            description("empty header")
+            display("{} tag without value found in line: {}", tag, line)
+        }
         #[doc="an unknown note type was found"]
         UnknownNoteType(line: u32) {
             description("unknown note type")
@@ -33,6 +39,16 @@ error_chain! {
         MissingEndIndicator {
             description("missing end indicator")
         }
+        #[doc="a header tag was found after the first note"]
+        MisplacedHeader(line: u32) {
+            description("misplaced header")
+            display("header tag found after the first note in line: {}", line)
+        }
+        #[doc="a header tag is not allowed by the version of the song"]
+        UnsupportedTag(line: u32, tag: &'static str) {
+            description("tag not supported by version")
+            display("{} tag in line {} is not supported by the version of the song", tag, line)
+        }
         #[doc="song file uses a feature that is not implemented"]
         NotImplemented(line: u32, feature: &'static str) {
             description("not implemented")
@@ -41,301 +57,528 @@ error_chain! {
     }
 }
 
-/// Parses the Header of a given Ultrastar Song and returns a Header struct
-///
-/// # Arguments
-/// * txt_str  - a &str that contains the song to parse
-///
-pub fn parse_txt_header_str(txt_str: &str) -> Result<Header> {
-    let mut opt_title = None;
-    let mut opt_artist = None;
-    let mut opt_bpm = None;
-    let mut opt_audio_path = None;
-
-    let mut opt_gap = None;
-    let mut opt_cover_path = None;
-    let mut opt_background_path = None;
-    let mut opt_video_path = None;
-    let mut opt_video_gap = None;
-    let mut opt_genre = None;
-    let mut opt_edition = None;
-    let mut opt_language = None;
-    let mut opt_year = None;
-    let mut opt_relative = None;
-    let mut opt_unknown: Option<HashMap<String, String>> = None;
-
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"#([A-Z1-3a-z]*):(.*)").unwrap();
+impl ErrorKind {
+    /// returns the line the problem was found in, if it belongs to a single line
+    pub fn line(&self) -> Option<u32> {
+        match *self {
+            ErrorKind::DuplicateHeader(line, _)
+            | ErrorKind::ValueError(line, _)
+            | ErrorKind::EmptyHeader(line, _)
+            | ErrorKind::UnknownNoteType(line)
+            | ErrorKind::ParserFailure(line)
+            | ErrorKind::MisplacedHeader(line)
+            | ErrorKind::UnsupportedTag(line, _)
+            | ErrorKind::NotImplemented(line, _) => Some(line),
+            _ => None,
+        }
     }
+}
 
-    for (line, line_count) in txt_str.lines().zip(1..) {
-        let cap = match RE.captures(line) {
+/// Describes a problem the lenient parser found and recovered from
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// how severe the problem is
+    pub severity: Severity,
+    /// the line the problem was found in
+    pub line: Option<u32>,
+    /// the kind of the problem
+    pub kind: ErrorKind,
+}
+
+// collects the problems found while parsing
+// in strict mode the first error aborts parsing, in lenient mode the parser recovers from it
+struct Problems {
+    lenient: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Problems {
+    fn strict() -> Self {
+        Problems {
+            lenient: false,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn lenient() -> Self {
+        Problems {
+            lenient: true,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // reports a problem that loses data, this is only Ok if the parser should recover from it
+    fn error(&mut self, kind: ErrorKind) -> Result<()> {
+        if self.lenient {
+            self.push(Severity::Error, kind);
+            Ok(())
+        } else {
+            Err(kind.into())
+        }
+    }
+
+    // reports a problem that does not lose any data, it never aborts parsing
+    fn warning(&mut self, kind: ErrorKind) {
+        self.push(Severity::Warning, kind);
+    }
+
+    // reports a problem that is an error in strict mode but does not lose any data otherwise
+    fn strict_error(&mut self, kind: ErrorKind) -> Result<()> {
+        if self.lenient {
+            self.warning(kind);
+            Ok(())
+        } else {
+            Err(kind.into())
+        }
+    }
+
+    fn push(&mut self, severity: Severity, kind: ErrorKind) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            line: kind.line(),
+            kind,
+        });
+    }
+}
+
+fn parse_float(value: &str) -> Option<f32> {
+    value.trim().replace(",", ".").parse().ok()
+}
+
+fn parse_int<T: FromStr>(value: &str) -> Option<T> {
+    value.trim().parse().ok()
+}
+
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value {
+        "YES" | "yes" => Some(true),
+        "NO" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.trim() {
+        "YES" | "yes" | "ON" | "on" => Some(true),
+        "NO" | "no" | "OFF" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+// stores the value of a tag if the tag was not found before, returns true if it was stored
+fn set_once<T, F>(
+    field: &mut Option<T>,
+    line_count: u32,
+    tag: &'static str,
+    problems: &mut Problems,
+    parse: F,
+) -> Result<bool>
+where
+    F: FnOnce() -> Option<T>,
+{
+    if field.is_some() {
+        problems.error(ErrorKind::DuplicateHeader(line_count, tag))?;
+        return Ok(false);
+    }
+    match parse() {
+        Some(x) => {
+            *field = Some(x);
+            Ok(true)
+        }
+        None => {
+            problems.error(ErrorKind::ValueError(line_count, tag))?;
+            Ok(false)
+        }
+    }
+}
+
+// the values of the header tags found so far
+#[derive(Default)]
+struct HeaderBuilder {
+    title: Option<String>,
+    artist: Option<String>,
+    bpm: Option<f32>,
+    mp3: Option<String>,
+    audio: Option<String>,
+
+    gap: Option<f32>,
+    cover_path: Option<Source>,
+    background_path: Option<Source>,
+    video_path: Option<Source>,
+    video_gap: Option<f32>,
+    genre: Option<String>,
+    edition: Option<String>,
+    language: Option<String>,
+    year: Option<u32>,
+    creator: Option<String>,
+    start: Option<f32>,
+    end: Option<i32>,
+    resolution: Option<i32>,
+    notes_gap: Option<i32>,
+    encoding: Option<String>,
+    preview_start: Option<f32>,
+    medley_start_beat: Option<i32>,
+    medley_end_beat: Option<i32>,
+    calc_medley: Option<bool>,
+    duet_singer_p1: Option<String>,
+    duet_singer_p2: Option<String>,
+    version: Option<Version>,
+    relative: Option<bool>,
+    unknown: Option<HashMap<String, String>>,
+
+    // tags that are not allowed by every version and the lines they are found in
+    restricted_tags: Vec<(u32, &'static str)>,
+}
+
+lazy_static! {
+    static ref TAG_RE: Regex = Regex::new(r"#([A-Z1-3a-z]*):(.*)").unwrap();
+}
+
+impl HeaderBuilder {
+    // parses a line of the header, returns false if the line is no header tag
+    fn parse_line(&mut self, line: &str, line_count: u32, problems: &mut Problems) -> Result<bool> {
+        let cap = match TAG_RE.captures(line) {
             Some(x) => x,
-            None => break,
+            None => return Ok(false),
         };
         let key = cap.get(1).unwrap().as_str();
         let value = cap.get(2).unwrap().as_str();
 
-        if value == "" {
-            //TODO: somehow warn about this
-            continue;
+        if value.is_empty() {
+            problems.warning(ErrorKind::EmptyHeader(line_count, String::from(key)));
+            return Ok(true);
         }
 
+        let text = || Some(String::from(value));
+        let source = || Some(Source::parse(value));
+        let float = || parse_float(value);
         match key {
+            "VERSION" => {
+                set_once(&mut self.version, line_count, "VERSION", problems, || {
+                    Version::parse(value)
+                })?;
+            }
             "TITLE" => {
-                if opt_title.is_none() {
-                    opt_title = Some(String::from(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "TITLE"));
-                }
+                set_once(&mut self.title, line_count, "TITLE", problems, text)?;
             }
             "ARTIST" => {
-                if opt_artist.is_none() {
-                    opt_artist = Some(String::from(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "ARTIST"));
-                }
+                set_once(&mut self.artist, line_count, "ARTIST", problems, text)?;
             }
             "MP3" => {
-                if opt_audio_path.is_none() {
-                    opt_audio_path = Some(Source::parse(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "MP3"));
-                }
+                set_once(&mut self.mp3, line_count, "MP3", problems, text)?;
+            }
+            "AUDIO" => {
+                set_once(&mut self.audio, line_count, "AUDIO", problems, text)?;
             }
             "BPM" => {
-                if opt_bpm.is_none() {
-                    opt_bpm = match value.trim().replace(",", ".").parse() {
-                        Ok(x) => Some(x),
-                        Err(_) => {
-                            bail!(ErrorKind::ValueError(line_count, "BPM"));
-                        }
-                    };
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "BPM"));
-                }
+                set_once(&mut self.bpm, line_count, "BPM", problems, float)?;
             }
 
             // Optional Header fields
             "GAP" => {
-                if opt_gap.is_none() {
-                    opt_gap = match value.trim().replace(",", ".").parse() {
-                        Ok(x) => Some(x),
-                        Err(_) => {
-                            bail!(ErrorKind::ValueError(line_count, "GAP"));
-                        }
-                    };
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "GAP"));
-                }
+                set_once(&mut self.gap, line_count, "GAP", problems, float)?;
             }
             "COVER" => {
-                if opt_cover_path.is_none() {
-                    opt_cover_path = Some(Source::parse(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "COVER"));
-                }
+                set_once(&mut self.cover_path, line_count, "COVER", problems, source)?;
             }
             "BACKGROUND" => {
-                if opt_background_path.is_none() {
-                    opt_background_path = Some(Source::parse(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "BACKGROUND"));
-                }
+                set_once(
+                    &mut self.background_path,
+                    line_count,
+                    "BACKGROUND",
+                    problems,
+                    source,
+                )?;
             }
             "VIDEO" => {
-                if opt_video_path.is_none() {
-                    opt_video_path = Some(Source::parse(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "VIDEO"));
-                }
+                set_once(&mut self.video_path, line_count, "VIDEO", problems, source)?;
             }
             "VIDEOGAP" => {
-                if opt_video_gap.is_none() {
-                    opt_video_gap = match value.trim().replace(",", ".").parse() {
-                        Ok(x) => Some(x),
-                        Err(_) => {
-                            bail!(ErrorKind::ValueError(line_count, "VIDEOGAP"));
-                        }
-                    };
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "VIDEOGAP"));
-                }
+                set_once(&mut self.video_gap, line_count, "VIDEOGAP", problems, float)?;
             }
             "GENRE" => {
-                if opt_genre.is_none() {
-                    opt_genre = Some(String::from(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "GENRE"));
-                }
+                set_once(&mut self.genre, line_count, "GENRE", problems, text)?;
             }
             "EDITION" => {
-                if opt_edition.is_none() {
-                    opt_edition = Some(String::from(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "EDITION"));
-                }
+                set_once(&mut self.edition, line_count, "EDITION", problems, text)?;
             }
             "LANGUAGE" => {
-                if opt_language.is_none() {
-                    opt_language = Some(String::from(value));
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "LANGUAGE"));
-                }
+                set_once(&mut self.language, line_count, "LANGUAGE", problems, text)?;
             }
             "YEAR" => {
-                if opt_year.is_none() {
-                    opt_year = match value.trim().parse() {
-                        Ok(x) => Some(x),
-                        Err(_) => {
-                            bail!(ErrorKind::ValueError(line_count, "YEAR"));
-                        }
-                    };
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "YEAR"));
+                set_once(&mut self.year, line_count, "YEAR", problems, || {
+                    parse_int(value)
+                })?;
+            }
+            "CREATOR" => {
+                set_once(&mut self.creator, line_count, "CREATOR", problems, text)?;
+            }
+            "START" => {
+                set_once(&mut self.start, line_count, "START", problems, float)?;
+            }
+            "END" => {
+                set_once(&mut self.end, line_count, "END", problems, || {
+                    parse_int(value)
+                })?;
+            }
+            "RESOLUTION" => {
+                if set_once(
+                    &mut self.resolution,
+                    line_count,
+                    "RESOLUTION",
+                    problems,
+                    || parse_int(value),
+                )? {
+                    self.restricted_tags.push((line_count, "RESOLUTION"));
+                }
+            }
+            "NOTESGAP" => {
+                if set_once(
+                    &mut self.notes_gap,
+                    line_count,
+                    "NOTESGAP",
+                    problems,
+                    || parse_int(value),
+                )? {
+                    self.restricted_tags.push((line_count, "NOTESGAP"));
+                }
+            }
+            "ENCODING" => {
+                if set_once(&mut self.encoding, line_count, "ENCODING", problems, text)? {
+                    self.restricted_tags.push((line_count, "ENCODING"));
                 }
             }
-            //TODO: check if relative changes line breaks
+            "PREVIEWSTART" => {
+                set_once(
+                    &mut self.preview_start,
+                    line_count,
+                    "PREVIEWSTART",
+                    problems,
+                    float,
+                )?;
+            }
+            "MEDLEYSTARTBEAT" => {
+                set_once(
+                    &mut self.medley_start_beat,
+                    line_count,
+                    "MEDLEYSTARTBEAT",
+                    problems,
+                    || parse_int(value),
+                )?;
+            }
+            "MEDLEYENDBEAT" => {
+                set_once(
+                    &mut self.medley_end_beat,
+                    line_count,
+                    "MEDLEYENDBEAT",
+                    problems,
+                    || parse_int(value),
+                )?;
+            }
+            "CALCMEDLEY" => {
+                set_once(
+                    &mut self.calc_medley,
+                    line_count,
+                    "CALCMEDLEY",
+                    problems,
+                    || parse_switch(value),
+                )?;
+            }
+            "DUETSINGERP1" | "P1" => {
+                set_once(
+                    &mut self.duet_singer_p1,
+                    line_count,
+                    "DUETSINGERP1",
+                    problems,
+                    text,
+                )?;
+            }
+            "DUETSINGERP2" | "P2" => {
+                set_once(
+                    &mut self.duet_singer_p2,
+                    line_count,
+                    "DUETSINGERP2",
+                    problems,
+                    text,
+                )?;
+            }
+            // relative line breaks are interpreted by TXTSong::to_absolute
             "RELATIVE" => {
-                if opt_relative.is_none() {
-                    opt_relative = match value {
-                        "YES" | "yes" => Some(true),
-                        "NO" | "no" => Some(false),
-                        _ => {
-                            bail!(ErrorKind::ValueError(line_count, "RELATIVE"));
-                        }
-                    }
-                } else {
-                    bail!(ErrorKind::DuplicateHeader(line_count, "RELATIVE"));
+                if set_once(&mut self.relative, line_count, "RELATIVE", problems, || {
+                    parse_yes_no(value)
+                })? {
+                    self.restricted_tags.push((line_count, "RELATIVE"));
                 }
             }
             // use hashmap to store unknown tags
             k => {
-                opt_unknown = match opt_unknown {
-                    Some(mut x) => {
-                        if !x.contains_key(k) {
-                            x.insert(String::from(k), String::from(value));
-                            Some(x)
-                        } else {
-                            bail!(ErrorKind::DuplicateHeader(line_count, "UNKNOWN"));
-                        }
-                    }
-                    None => {
-                        let mut unknown = HashMap::new();
-                        unknown.insert(String::from(k), String::from(value));
-                        Some(unknown)
-                    }
-                };
+                let unknown = self.unknown.get_or_insert_with(HashMap::new);
+                if !unknown.contains_key(k) {
+                    unknown.insert(String::from(k), String::from(value));
+                } else {
+                    problems.error(ErrorKind::DuplicateHeader(line_count, "UNKNOWN"))?;
+                }
             }
         };
+        Ok(true)
     }
 
-    // build header from Options
-    if let (Some(title), Some(artist), Some(bpm), Some(audio_path)) =
-        (opt_title, opt_artist, opt_bpm, opt_audio_path)
-    {
-        let header = Header {
-            title,
-            artist,
-            bpm,
-            audio_path,
-
-            gap: opt_gap,
-            cover_path: opt_cover_path,
-            background_path: opt_background_path,
-            video_path: opt_video_path,
-            video_gap: opt_video_gap,
-            genre: opt_genre,
-            edition: opt_edition,
-            language: opt_language,
-            year: opt_year,
-            relative: opt_relative,
-            unknown: opt_unknown,
-        };
-        // header complete
-        Ok(header)
-    } else {
-        // essential field is missing
-        bail!(ErrorKind::MissingEssential)
+    // builds the header from the tags found, lenient parsing leaves missing essentials empty
+    fn build(mut self, problems: &mut Problems) -> Result<Header> {
+        // apply the rules of the version
+        let version = self.version.unwrap_or(Version::Legacy);
+        for &(line_count, tag) in self.restricted_tags.iter() {
+            if !version.allows_tag(tag) {
+                // the value is kept, so nothing is lost when parsing leniently
+                problems.strict_error(ErrorKind::UnsupportedTag(line_count, tag))?;
+            }
+        }
+        let mut audio_path = self.mp3.as_deref().map(Source::parse);
+        if let Some(audio) = self.audio {
+            if version.uses_audio_tag() {
+                audio_path = Some(Source::parse(&audio));
+                // #MP3 is only a fallback for players that do not know #AUDIO, keep it as unknown tag
+                if let Some(mp3) = self.mp3 {
+                    self.unknown
+                        .get_or_insert_with(HashMap::new)
+                        .insert(String::from("MP3"), mp3);
+                }
+            } else {
+                // #AUDIO is not a tag of this version, keep it as unknown tag
+                self.unknown
+                    .get_or_insert_with(HashMap::new)
+                    .insert(String::from("AUDIO"), audio);
+            }
+        }
+
+        if self.title.is_none()
+            || self.artist.is_none()
+            || self.bpm.is_none()
+            || audio_path.is_none()
+        {
+            // essential field is missing
+            problems.error(ErrorKind::MissingEssential)?;
+        }
+
+        Ok(Header {
+            title: self.title.unwrap_or_default(),
+            artist: self.artist.unwrap_or_default(),
+            bpm: self.bpm.unwrap_or_default(),
+            audio_path: audio_path.unwrap_or_else(|| Source::parse("")),
+
+            gap: self.gap,
+            cover_path: self.cover_path,
+            background_path: self.background_path,
+            video_path: self.video_path,
+            video_gap: self.video_gap,
+            genre: self.genre,
+            edition: self.edition,
+            language: self.language,
+            year: self.year,
+            creator: self.creator,
+            start: self.start,
+            end: self.end,
+            resolution: self.resolution,
+            notes_gap: self.notes_gap,
+            encoding: self.encoding,
+            preview_start: self.preview_start,
+            medley_start_beat: self.medley_start_beat,
+            medley_end_beat: self.medley_end_beat,
+            calc_medley: self.calc_medley,
+            duet_singer_p1: self.duet_singer_p1,
+            duet_singer_p2: self.duet_singer_p2,
+            version,
+            relative: self.relative,
+            unknown: self.unknown,
+        })
     }
 }
 
-/// Parses the lyric lines of a given Ultarstar song and returns a vector of Line structs
-///
-/// # Arguments
-/// * txt_str  - a &str that contains the song to parse
-///
-pub fn parse_txt_lines_str(txt_str: &str) -> Result<Vec<Line>> {
-    lazy_static! {
-        static ref LINE_RE: Regex = Regex::new("^-\\s?(-?[0-9]+)\\s*$").unwrap();
-        static ref LREL_RE: Regex = Regex::new("^-\\s?(-?[0-9]+)\\s+(-?[0-9]+)").unwrap();
-        static ref NOTE_RE: Regex =
-            Regex::new("^(.)\\s*(-?[0-9]+)\\s+(-?[0-9]+)\\s+(-?[0-9]+)\\s?(.*)").unwrap();
-        static ref DUET_RE: Regex = Regex::new("^P\\s?(-?[0-9]+)").unwrap();
-    }
+// the lines parsed so far
+struct LinesBuilder {
+    lines: Vec<Line>,
+    current_line: Line,
+    found_end_indicator: bool,
+}
 
-    let mut lines_vec = Vec::new();
-    let mut current_line = Line {
-        start: 0,
-        rel: None,
-        notes: Vec::new(),
-    };
+impl LinesBuilder {
+    fn new() -> Self {
+        LinesBuilder {
+            lines: Vec::new(),
+            current_line: Line {
+                start: 0,
+                rel: None,
+                notes: Vec::new(),
+            },
+            found_end_indicator: false,
+        }
+    }
 
-    let mut found_end_indicator = false;
-    for (line, line_count) in txt_str.lines().zip(1..) {
-        // ignore empty lines
-        if line == "" {
-            continue;
+    // parses a line of the songs body, lenient parsing skips lines it can not parse
+    fn parse_line(&mut self, line: &str, line_count: u32, problems: &mut Problems) -> Result<()> {
+        lazy_static! {
+            static ref LINE_RE: Regex = Regex::new("^-\\s?(-?[0-9]+)\\s*$").unwrap();
+            static ref LREL_RE: Regex = Regex::new("^-\\s?(-?[0-9]+)\\s+(-?[0-9]+)").unwrap();
+            static ref NOTE_RE: Regex =
+                Regex::new("^(.)\\s*(-?[0-9]+)\\s+(-?[0-9]+)\\s+(-?[0-9]+)\\s?(.*)").unwrap();
+            static ref DUET_RE: Regex = Regex::new("^P\\s?(-?[0-9]+)").unwrap();
+            static ref BPM_RE: Regex = Regex::new("^B\\s*(-?[0-9]+)\\s+(\\S+)\\s*$").unwrap();
         }
 
         let first_char = match line.chars().next() {
             Some(x) => x,
-            None => bail!(ErrorKind::ParserFailure(line_count)),
+            None => return problems.error(ErrorKind::ParserFailure(line_count)),
         };
 
-        // ignore header
-        if first_char == '#' {
-            continue;
-        }
-
-        // not implemented
+        // current line is a bpm change
         if first_char == 'B' {
-            bail!(ErrorKind::NotImplemented(line_count, "variable bpm"));
+            let cap = match BPM_RE.captures(line) {
+                Some(x) => x,
+                None => return problems.error(ErrorKind::ParserFailure(line_count)),
+            };
+            let change_beat = match cap.get(1).unwrap().as_str().parse() {
+                Ok(x) => x,
+                Err(_) => {
+                    return problems.error(ErrorKind::ValueError(line_count, "bpm change beat"));
+                }
+            };
+            let change_bpm = match parse_float(cap.get(2).unwrap().as_str()) {
+                Some(x) => x,
+                None => {
+                    return problems.error(ErrorKind::ValueError(line_count, "bpm change"));
+                }
+            };
+            self.current_line.notes.push(Note::BpmChange {
+                beat: change_beat,
+                bpm: change_bpm,
+            });
+            return Ok(());
         }
 
         // stop parsing after end symbol
         if first_char == 'E' {
-            lines_vec.push(current_line);
-            found_end_indicator = true;
-            break;
+            self.found_end_indicator = true;
+            return Ok(());
         }
 
         // current line is a note
-        if NOTE_RE.is_match(line) {
-            let cap = NOTE_RE.captures(line).unwrap();
-
+        if let Some(cap) = NOTE_RE.captures(line) {
             let note_start = match cap.get(2).unwrap().as_str().parse() {
                 Ok(x) => x,
                 Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "note start"));
+                    return problems.error(ErrorKind::ValueError(line_count, "note start"));
                 }
             };
             let note_duration = match cap.get(3).unwrap().as_str().parse() {
-                Ok(x) => {
-                    if x >= 0 {
-                        x
-                    } else {
-                        bail!(ErrorKind::ValueError(line_count, "note duration"));
-                    }
-                }
-                Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "note duration"));
+                Ok(x) if x >= 0 => x,
+                _ => {
+                    return problems.error(ErrorKind::ValueError(line_count, "note duration"));
                 }
             };
             let note_pitch = match cap.get(4).unwrap().as_str().parse() {
                 Ok(x) => x,
                 Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "note pitch"));
+                    return problems.error(ErrorKind::ValueError(line_count, "note pitch"));
                 }
             };
             let note_text = cap.get(5).unwrap().as_str();
@@ -359,81 +602,203 @@ pub fn parse_txt_lines_str(txt_str: &str) -> Result<Vec<Line>> {
                     pitch: note_pitch,
                     text: String::from(note_text),
                 },
-                _ => bail!(ErrorKind::UnknownNoteType(line_count)),
+                "R" => Note::Rap {
+                    start: note_start,
+                    duration: note_duration,
+                    pitch: note_pitch,
+                    text: String::from(note_text),
+                },
+                "G" => Note::RapGolden {
+                    start: note_start,
+                    duration: note_duration,
+                    pitch: note_pitch,
+                    text: String::from(note_text),
+                },
+                _ => return problems.error(ErrorKind::UnknownNoteType(line_count)),
             };
 
-            current_line.notes.push(note);
-            continue;
+            self.current_line.notes.push(note);
+            return Ok(());
         }
 
         // current line is a line break
-        if LINE_RE.is_match(line) {
-            // push old line to the Line vector and prepare new line
-            lines_vec.push(current_line);
-            let cap = LINE_RE.captures(line).unwrap();
+        if let Some(cap) = LINE_RE.captures(line) {
             let line_start = match cap.get(1).unwrap().as_str().parse() {
                 Ok(x) => x,
                 Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "line start"));
+                    return problems.error(ErrorKind::ValueError(line_count, "line start"));
                 }
             };
-            current_line = Line {
-                start: line_start,
-                rel: None,
-                notes: Vec::new(),
-            };
-            continue;
+            self.break_line(line_start, None);
+            return Ok(());
         }
 
         // current line is a relative line break
-        if LREL_RE.is_match(line) {
-            // push old line to the Line vector and prepare new line
-            lines_vec.push(current_line);
-            let cap = LREL_RE.captures(line).unwrap();
+        if let Some(cap) = LREL_RE.captures(line) {
             let line_start = match cap.get(1).unwrap().as_str().parse() {
                 Ok(x) => x,
                 Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "line start"));
+                    return problems.error(ErrorKind::ValueError(line_count, "line start"));
                 }
             };
             let line_rel = match cap.get(2).unwrap().as_str().parse() {
                 Ok(x) => x,
                 Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "line rel"));
+                    return problems.error(ErrorKind::ValueError(line_count, "line rel"));
                 }
             };
-            current_line = Line {
-                start: line_start,
-                rel: Some(line_rel),
-                notes: Vec::new(),
-            };
-            continue;
+            self.break_line(line_start, Some(line_rel));
+            return Ok(());
         }
 
-        if DUET_RE.is_match(line) {
-            let cap = DUET_RE.captures(line).unwrap();
+        if let Some(cap) = DUET_RE.captures(line) {
             let note = match cap.get(1).unwrap().as_str().parse() {
-                Ok(x) => {
-                    if x >= 1 && x <= 3 {
-                        Note::PlayerChange { player: x }
-                    } else {
-                        bail!(ErrorKind::ValueError(line_count, "player change"));
-                    }
-                }
-                Err(_) => {
-                    bail!(ErrorKind::ValueError(line_count, "player change"));
+                Ok(x) if (1..=3).contains(&x) => Note::PlayerChange { player: x },
+                _ => {
+                    return problems.error(ErrorKind::ValueError(line_count, "player change"));
                 }
             };
-            current_line.notes.push(note);
-            continue;
+            self.current_line.notes.push(note);
+            Ok(())
         } else {
             // unknown line
-            bail!(ErrorKind::ParserFailure(line_count));
+            problems.error(ErrorKind::ParserFailure(line_count))
         }
     }
-    if found_end_indicator {
-        Ok(lines_vec)
-    } else {
-        bail!(ErrorKind::MissingEndIndicator);
+
+    // pushes the current line to the Line vector and prepares a new line
+    fn break_line(&mut self, start: i32, rel: Option<i32>) {
+        let new_line = Line {
+            start,
+            rel,
+            notes: Vec::new(),
+        };
+        let old_line = std::mem::replace(&mut self.current_line, new_line);
+        self.lines.push(old_line);
+    }
+
+    // returns the lines, lenient parsing accepts a song without end indicator
+    fn build(mut self, problems: &mut Problems) -> Result<Vec<Line>> {
+        if !self.found_end_indicator {
+            problems.strict_error(ErrorKind::MissingEndIndicator)?;
+        }
+        self.lines.push(self.current_line);
+        Ok(self.lines)
     }
 }
+
+fn parse_header(txt_str: &str, problems: &mut Problems) -> Result<Header> {
+    let mut header = HeaderBuilder::default();
+    for (line, line_count) in txt_str.lines().zip(1..) {
+        if !header.parse_line(line, line_count, problems)? {
+            break;
+        }
+    }
+    header.build(problems)
+}
+
+fn parse_lines(txt_str: &str, problems: &mut Problems) -> Result<Vec<Line>> {
+    let mut lines = LinesBuilder::new();
+    for (line, line_count) in txt_str.lines().zip(1..) {
+        // ignore empty lines and the header
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        lines.parse_line(line, line_count, problems)?;
+        if lines.found_end_indicator {
+            break;
+        }
+    }
+    lines.build(problems)
+}
+
+// parses a single line of a songs body that describes a note
+pub(crate) fn parse_note_str(line: &str, line_count: u32) -> Result<Note> {
+    let mut lines = LinesBuilder::new();
+    lines.parse_line(line, line_count, &mut Problems::strict())?;
+    match lines.current_line.notes.pop() {
+        Some(note) => Ok(note),
+        None => bail!(ErrorKind::ParserFailure(line_count)),
+    }
+}
+
+fn parse_song(txt_str: &str, problems: &mut Problems) -> Result<TXTSong> {
+    let mut header = HeaderBuilder::default();
+    let mut lines = LinesBuilder::new();
+    let mut in_header = true;
+    // a byte order mark is not part of the first tag
+    let txt_str = txt_str.trim_start_matches('\u{feff}');
+    for (line, line_count) in txt_str.lines().zip(1..) {
+        // ignore empty lines
+        if line.is_empty() {
+            continue;
+        }
+        // lines starting with "#" that are no header tags are skipped as comments
+        if line.starts_with('#') {
+            if !in_header {
+                if TAG_RE.is_match(line) {
+                    problems.error(ErrorKind::MisplacedHeader(line_count))?;
+                }
+            } else {
+                header.parse_line(line, line_count, problems)?;
+            }
+            continue;
+        }
+        // the header ends with the first line that is no tag
+        in_header = false;
+        lines.parse_line(line, line_count, problems)?;
+        if lines.found_end_indicator {
+            break;
+        }
+    }
+    Ok(TXTSong {
+        header: header.build(problems)?,
+        lines: lines.build(problems)?,
+    })
+}
+
+/// Parses the Header of a given Ultrastar Song and returns a Header struct
+///
+/// # Arguments
+/// * txt_str  - a &str that contains the song to parse
+///
+pub fn parse_txt_header_str(txt_str: &str) -> Result<Header> {
+    parse_header(txt_str, &mut Problems::strict())
+}
+
+/// Parses the lyric lines of a given Ultarstar song and returns a vector of Line structs
+///
+/// # Arguments
+/// * txt_str  - a &str that contains the song to parse
+///
+pub fn parse_txt_lines_str(txt_str: &str) -> Result<Vec<Line>> {
+    parse_lines(txt_str, &mut Problems::strict())
+}
+
+/// Parses a given Ultrastar song in a single pass and returns a TXTSong struct
+///
+/// Other than parsing the header and the lines on their own,
+/// this fails if a header tag is found after the first note.
+/// Lines starting with "#" that are no header tags are skipped as comments.
+///
+/// # Arguments
+/// * txt_str  - a &str that contains the song to parse
+///
+pub fn parse_txt_song_str(txt_str: &str) -> Result<TXTSong> {
+    parse_song(txt_str, &mut Problems::strict())
+}
+
+/// Parses a given Ultrastar song without giving up on the first problem
+///
+/// Tags and lines that can not be parsed are skipped, missing essential tags are left empty.
+/// Every problem found is returned as a Diagnostic next to the best-effort TXTSong.
+///
+/// # Arguments
+/// * txt_str  - a &str that contains the song to parse
+///
+pub fn parse_txt_song_str_lenient(txt_str: &str) -> (TXTSong, Vec<Diagnostic>) {
+    let mut problems = Problems::lenient();
+    // lenient parsing recovers from every problem
+    let song = parse_song(txt_str, &mut problems).unwrap();
+    (song, problems.diagnostics)
+}
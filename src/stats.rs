@@ -0,0 +1,146 @@
+use crate::duet::Player;
+use crate::structs::{Note, TXTSong};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Describes the statistics of a song
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct SongStats {
+    /// the sum of the durations of all notes in ms
+    pub singing_time_ms: f32,
+    /// the number of regular notes
+    pub regular_notes: usize,
+    /// the number of golden notes
+    pub golden_notes: usize,
+    /// the number of freestyle notes
+    pub freestyle_notes: usize,
+    /// the number of rap notes
+    pub rap_notes: usize,
+    /// the number of golden rap notes
+    pub rap_golden_notes: usize,
+    /// the share of golden and golden rap notes in all notes, between 0 and 1
+    pub golden_ratio: f32,
+    /// the lowest pitch of the sung notes
+    pub min_pitch: Option<i32>,
+    /// the highest pitch of the sung notes
+    pub max_pitch: Option<i32>,
+    /// the median pitch of the sung notes
+    pub median_pitch: Option<i32>,
+    /// the number of notes per second between the start of the first and the end of the last note
+    pub notes_per_second: f32,
+    /// the largest interval in semitones between two sung notes of the same player
+    pub max_interval: i32,
+    /// the average interval in semitones between two sung notes of the same player
+    pub average_interval: f32,
+    /// the number of lines
+    pub line_count: usize,
+    /// the share of the first player in the singing time of both players of a duet
+    ///
+    /// 0.5 means both players sing the same time, None if the song is not a duet.
+    pub duet_balance: Option<f32>,
+}
+
+impl SongStats {
+    /// returns the number of all notes
+    pub fn note_count(&self) -> usize {
+        self.regular_notes
+            + self.golden_notes
+            + self.freestyle_notes
+            + self.rap_notes
+            + self.rap_golden_notes
+    }
+
+    /// returns the range of the sung pitches in semitones
+    pub fn pitch_range(&self) -> i32 {
+        match (self.min_pitch, self.max_pitch) {
+            (Some(min), Some(max)) => max - min,
+            _ => 0,
+        }
+    }
+
+    /// Returns a heuristic difficulty rating between 0 (easy) and 10 (hard)
+    ///
+    /// The rating is meant to sort songs, it weights the speed of the notes with 4 points,
+    /// the pitch range with 3 points and the average interval between notes with 3 points.
+    pub fn difficulty(&self) -> f32 {
+        let speed = (self.notes_per_second / 5.0).min(1.0) * 4.0;
+        let range = (self.pitch_range() as f32 / 24.0).min(1.0) * 3.0;
+        let intervals = (self.average_interval / 6.0).min(1.0) * 3.0;
+        speed + range + intervals
+    }
+}
+
+impl TXTSong {
+    /// computes the statistics of the song
+    pub fn stats(&self) -> SongStats {
+        let mut stats = SongStats {
+            line_count: self.lines.len(),
+            median_pitch: self.median_pitch(),
+            ..SongStats::default()
+        };
+        let mut player = Player::One;
+        // the pitch of the previous sung note of every player
+        let mut previous_pitches: [Option<i32>; 3] = [None; 3];
+        let mut intervals = Vec::new();
+        let mut player_times = [0.0; 3];
+        let mut song_start = f32::MAX;
+        let mut song_end = f32::MIN;
+
+        let converter = self.time_converter();
+        for (line_index, line) in self.lines.iter().enumerate() {
+            let offset = self.line_offset(line_index);
+            for note in line.notes.iter() {
+                match *note {
+                    Note::Regular { .. } => stats.regular_notes += 1,
+                    Note::Golden { .. } => stats.golden_notes += 1,
+                    Note::Freestyle { .. } => stats.freestyle_notes += 1,
+                    Note::Rap { .. } => stats.rap_notes += 1,
+                    Note::RapGolden { .. } => stats.rap_golden_notes += 1,
+                    Note::PlayerChange { player: number } => {
+                        player = Player::from_number(number).unwrap_or(player);
+                        continue;
+                    }
+                    Note::BpmChange { .. } => continue,
+                }
+                if let (Some(start), Some(duration)) = (note.start(), note.duration()) {
+                    let start_beat = offset + start;
+                    let start = converter.ms_at_beat(start_beat as f32);
+                    let end = converter.ms_at_beat((start_beat + duration) as f32);
+                    stats.singing_time_ms += end - start;
+                    player_times[player.number() as usize - 1] += end - start;
+                    song_start = song_start.min(start);
+                    song_end = song_end.max(end);
+                }
+                let pitch = match *note {
+                    Note::Regular { pitch, .. } | Note::Golden { pitch, .. } => pitch,
+                    _ => continue,
+                };
+                stats.min_pitch = Some(stats.min_pitch.map_or(pitch, |x| x.min(pitch)));
+                stats.max_pitch = Some(stats.max_pitch.map_or(pitch, |x| x.max(pitch)));
+                let previous_pitch = &mut previous_pitches[player.number() as usize - 1];
+                if let Some(previous_pitch) = *previous_pitch {
+                    intervals.push((pitch - previous_pitch).abs());
+                }
+                *previous_pitch = Some(pitch);
+            }
+        }
+
+        let note_count = stats.note_count();
+        if note_count > 0 {
+            stats.golden_ratio =
+                (stats.golden_notes + stats.rap_golden_notes) as f32 / note_count as f32;
+        }
+        if song_end > song_start {
+            stats.notes_per_second = note_count as f32 * 1000.0 / (song_end - song_start);
+        }
+        stats.max_interval = intervals.iter().cloned().max().unwrap_or(0);
+        if !intervals.is_empty() {
+            stats.average_interval = intervals.iter().sum::<i32>() as f32 / intervals.len() as f32;
+        }
+        if self.is_duet() && player_times[0] + player_times[1] > 0.0 {
+            stats.duet_balance = Some(player_times[0] / (player_times[0] + player_times[1]));
+        }
+        stats
+    }
+}
@@ -49,6 +49,89 @@ impl Source {
     }
 }
 
+/// Describes the version of the Ultrastar file format a song is written in
+///
+/// The versions differ in the tags they allow:
+/// * Legacy files (without a #VERSION tag) name the audio file with #MP3 and may use #RELATIVE,
+///   #ENCODING, #RESOLUTION and #NOTESGAP
+/// * 1.0.0 drops relative timing, other encodings than UTF-8 and the editor settings
+/// * 1.1.0 replaces #MP3 with #AUDIO, #MP3 is only read as a fallback, and names the duet
+///   singers with #P1 and #P2 instead of #DUETSINGERP1 and #DUETSINGERP2
+/// * 1.2.0 uses the same tags as 1.1.0
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Version {
+    /// a file without a #VERSION tag
+    Legacy,
+    /// version 1.0.0 of the file format
+    V1_0_0,
+    /// version 1.1.0 of the file format
+    V1_1_0,
+    /// version 1.2.0 of the file format
+    V1_2_0,
+}
+
+impl Version {
+    /// convert the version to the value of a #VERSION tag, legacy files have no such tag
+    pub fn to_str(self) -> Option<&'static str> {
+        match self {
+            Version::Legacy => None,
+            Version::V1_0_0 => Some("1.0.0"),
+            Version::V1_1_0 => Some("1.1.0"),
+            Version::V1_2_0 => Some("1.2.0"),
+        }
+    }
+
+    /// attempt to parse the value of a #VERSION tag, the patch level is ignored
+    ///
+    /// Versions before 1.0.0 are not supported and return None, like unknown versions.
+    pub fn parse(input_value: &str) -> Option<Self> {
+        let mut parts = input_value.trim().split('.');
+        let major: u32 = parts.next()?.parse().ok()?;
+        let minor: u32 = parts.next().unwrap_or("0").parse().ok()?;
+        if let Some(patch) = parts.next() {
+            patch.parse::<u32>().ok()?;
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        match (major, minor) {
+            (1, 0) => Some(Version::V1_0_0),
+            (1, 1) => Some(Version::V1_1_0),
+            (1, 2) => Some(Version::V1_2_0),
+            _ => None,
+        }
+    }
+
+    /// returns true if the audio file is given by the #AUDIO tag instead of #MP3
+    pub fn uses_audio_tag(self) -> bool {
+        self >= Version::V1_1_0
+    }
+
+    /// returns true if the duet singers are given by #P1 and #P2
+    pub fn uses_short_duet_tags(self) -> bool {
+        self >= Version::V1_1_0
+    }
+
+    /// returns true if the version allows the given tag
+    pub fn allows_tag(self, tag: &str) -> bool {
+        match tag {
+            "RELATIVE" | "ENCODING" | "RESOLUTION" | "NOTESGAP" => self == Version::Legacy,
+            _ => true,
+        }
+    }
+}
+
+/// Describes how severe a problem found in a song is
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Severity {
+    /// the song does not follow the file format, but can be used as it is
+    Warning,
+    /// parts of the song are lost or can not be used
+    Error,
+}
+
 /// Describes the Header of an Ultrastar Song
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(PartialEq, Clone, Debug)]
@@ -83,28 +166,89 @@ pub struct Header {
     /// the year the song is from
     pub year: Option<u32>,
 
-    /* header fields todo
-    // these are header fields parsed by ultrastar deluxe
-    // they might be added if the need arises or my understanding of them grows
-    pub creator: Option<String>,  // CREATOR
-    pub start: Option<f32>,       // START
-    pub end: Option<i32>,         // END
-    pub resolution: Option<i32>,  // RESOLUTION
-    pub notes_gap: Option<i32>,   // NOTESGAP
-    pub encoding: Option<String>, // ENCODING
-    pub preview_start: Option<i32>, // PREVIEWSTART
-    pub medley_start_beat: Option<i32>, // MEDLEYSTARTBEAT
-    pub medley_end_beat: Option<i32>,   // MEDLEYENDBEAT
-    pub calc_medley: Option<Bool>,      // CALCMEDLEY
-    pub duet_singer_p1: Option<String>, // DUETSINGERP1 / P1
-    pub duet_singer_p2: Option<String>, // DUETSINGERP2 / P2:
-    */
+    // header fields parsed by ultrastar deluxe
+    /// the creator of the song file
+    pub creator: Option<String>,
+    /// the time in seconds at which the playback of the song starts
+    pub start: Option<f32>,
+    /// the time in milliseconds at which the playback of the song ends
+    pub end: Option<i32>,
+    /// the resolution of the editor grid in beats
+    pub resolution: Option<i32>,
+    /// the offset of the editor grid in beats
+    pub notes_gap: Option<i32>,
+    /// the encoding the song file is stored in
+    pub encoding: Option<String>,
+    /// the time in seconds at which the preview of the song starts
+    pub preview_start: Option<f32>,
+    /// the beat at which the medley part of the song starts
+    pub medley_start_beat: Option<i32>,
+    /// the beat at which the medley part of the song ends
+    pub medley_end_beat: Option<i32>,
+    /// should the medley part of the song be calculated if it is not given
+    pub calc_medley: Option<bool>,
+    /// the name of the first singer of a duet
+    pub duet_singer_p1: Option<String>,
+    /// the name of the second singer of a duet
+    pub duet_singer_p2: Option<String>,
+
+    /// the version of the file format
+    pub version: Version,
     /// is the timing format of the song relative
     pub relative: Option<bool>,
     /// a hashmap that contains all tags that are unknown to the parser
     pub unknown: Option<HashMap<String, String>>,
 }
 
+impl Header {
+    /// creates a header from the mandatory tags, all other tags are not set
+    pub fn new(artist: &str, title: &str, bpm: f32, audio_path: Source) -> Self {
+        Header {
+            artist: String::from(artist),
+            title: String::from(title),
+            bpm,
+            audio_path,
+            gap: None,
+            cover_path: None,
+            background_path: None,
+            video_path: None,
+            video_gap: None,
+            genre: None,
+            edition: None,
+            language: None,
+            year: None,
+            creator: None,
+            start: None,
+            end: None,
+            resolution: None,
+            notes_gap: None,
+            encoding: None,
+            preview_start: None,
+            medley_start_beat: None,
+            medley_end_beat: None,
+            calc_medley: None,
+            duet_singer_p1: None,
+            duet_singer_p2: None,
+            version: Version::Legacy,
+            relative: None,
+            unknown: None,
+        }
+    }
+
+    /// Returns the audio path that is assumed for a song without an audio tag
+    ///
+    /// The path is "{artist} - {title}.mp3", the usual name of the audio file in a song folder.
+    /// It is used by the importers of LRC and MIDI files, which contain no audio file.
+    ///
+    /// # Arguments
+    /// * artist - the artist of the song
+    /// * title - the title of the song
+    ///
+    pub fn default_audio_path(artist: &str, title: &str) -> Source {
+        Source::parse(&format!("{} - {}.mp3", artist, title))
+    }
+}
+
 /// Describes an Ultrastar song as the combination of its Header and its Lines
 #[derive(PartialEq, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -115,6 +259,30 @@ pub struct TXTSong {
     pub lines: Vec<Line>,
 }
 
+impl TXTSong {
+    /// returns the tempo map of the song as a list of (beat, bpm) pairs sorted by beat
+    ///
+    /// The first entry is the bpm from the header starting at beat 0, followed by the bpm
+    /// changes of the song. The beats are absolute, even if the song uses relative timing.
+    pub fn tempo_map(&self) -> Vec<(i32, f32)> {
+        let relative = self.header.relative.unwrap_or(false);
+        let mut tempo_map = vec![(0, self.header.bpm)];
+        let mut offset = 0;
+        for line in self.lines.iter() {
+            if relative {
+                offset += line.rel.unwrap_or(0);
+            }
+            for note in line.notes.iter() {
+                if let Note::BpmChange { beat, bpm } = *note {
+                    tempo_map.push((offset + beat, bpm));
+                }
+            }
+        }
+        tempo_map.sort_by_key(|&(beat, _)| beat);
+        tempo_map
+    }
+}
+
 /// Describes the different types of notes the parser might encounter
 #[derive(PartialEq, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -152,6 +320,28 @@ pub enum Note {
         /// text or syllable of the note
         text: String,
     },
+    /// a rap note (spoken, only the timing is rated)
+    Rap {
+        /// start of the note
+        start: i32,
+        /// duration of the note
+        duration: i32,
+        /// pitch of the note (in semitones with C2 being 0)
+        pitch: i32, //pitch is not rated but kept to not lose data from orig file
+        /// text or syllable of the note
+        text: String,
+    },
+    /// a golden rap note (rap note with 2x points)
+    RapGolden {
+        /// start of the note
+        start: i32,
+        /// duration of the note
+        duration: i32,
+        /// pitch of the note (in semitones with C2 being 0)
+        pitch: i32,
+        /// text or syllable of the note
+        text: String,
+    },
     /// player change indicator for duet mode
     PlayerChange {
         /// player to change to
@@ -160,6 +350,13 @@ pub enum Note {
         /// 3 = Both
         player: i32,
     },
+    /// bpm change indicator for songs with variable bpm
+    BpmChange {
+        /// beat at which the new bpm takes effect
+        beat: i32,
+        /// the new beats per minute of the song
+        bpm: f32,
+    },
 }
 
 impl Note {
@@ -168,8 +365,10 @@ impl Note {
         match *self {
             Note::Regular { start, .. }
             | Note::Golden { start, .. }
-            | Note::Freestyle { start, .. } => Some(start),
-            Note::PlayerChange { .. } => None,
+            | Note::Freestyle { start, .. }
+            | Note::Rap { start, .. }
+            | Note::RapGolden { start, .. } => Some(start),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
         }
     }
 
@@ -178,8 +377,10 @@ impl Note {
         match *self {
             Note::Regular { duration, .. }
             | Note::Golden { duration, .. }
-            | Note::Freestyle { duration, .. } => Some(duration),
-            Note::PlayerChange { .. } => None,
+            | Note::Freestyle { duration, .. }
+            | Note::Rap { duration, .. }
+            | Note::RapGolden { duration, .. } => Some(duration),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
         }
     }
 
@@ -188,8 +389,10 @@ impl Note {
         match *self {
             Note::Regular { pitch, .. }
             | Note::Golden { pitch, .. }
-            | Note::Freestyle { pitch, .. } => Some(pitch),
-            Note::PlayerChange { .. } => None,
+            | Note::Freestyle { pitch, .. }
+            | Note::Rap { pitch, .. }
+            | Note::RapGolden { pitch, .. } => Some(pitch),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
         }
     }
 
@@ -198,8 +401,68 @@ impl Note {
         match *self {
             Note::Regular { ref text, .. }
             | Note::Golden { ref text, .. }
-            | Note::Freestyle { ref text, .. } => Some(text),
-            Note::PlayerChange { .. } => None,
+            | Note::Freestyle { ref text, .. }
+            | Note::Rap { ref text, .. }
+            | Note::RapGolden { ref text, .. } => Some(text),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
+        }
+    }
+
+    /// returns a mutable reference to the start value of the note
+    pub fn start_mut(&mut self) -> Option<&mut i32> {
+        match *self {
+            Note::Regular { ref mut start, .. }
+            | Note::Golden { ref mut start, .. }
+            | Note::Freestyle { ref mut start, .. }
+            | Note::Rap { ref mut start, .. }
+            | Note::RapGolden { ref mut start, .. } => Some(start),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
+        }
+    }
+
+    /// returns a mutable reference to the duration value of the note
+    pub fn duration_mut(&mut self) -> Option<&mut i32> {
+        match *self {
+            Note::Regular {
+                ref mut duration, ..
+            }
+            | Note::Golden {
+                ref mut duration, ..
+            }
+            | Note::Freestyle {
+                ref mut duration, ..
+            }
+            | Note::Rap {
+                ref mut duration, ..
+            }
+            | Note::RapGolden {
+                ref mut duration, ..
+            } => Some(duration),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
+        }
+    }
+
+    /// returns a mutable reference to the pitch value of the note
+    pub fn pitch_mut(&mut self) -> Option<&mut i32> {
+        match *self {
+            Note::Regular { ref mut pitch, .. }
+            | Note::Golden { ref mut pitch, .. }
+            | Note::Freestyle { ref mut pitch, .. }
+            | Note::Rap { ref mut pitch, .. }
+            | Note::RapGolden { ref mut pitch, .. } => Some(pitch),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
+        }
+    }
+
+    /// returns a mutable reference to the text of the note
+    pub fn text_mut(&mut self) -> Option<&mut String> {
+        match *self {
+            Note::Regular { ref mut text, .. }
+            | Note::Golden { ref mut text, .. }
+            | Note::Freestyle { ref mut text, .. }
+            | Note::Rap { ref mut text, .. }
+            | Note::RapGolden { ref mut text, .. } => Some(text),
+            Note::PlayerChange { .. } | Note::BpmChange { .. } => None,
         }
     }
 
@@ -207,7 +470,12 @@ impl Note {
     pub fn player(&self) -> Option<i32> {
         match *self {
             Note::PlayerChange { player, .. } => Some(player),
-            Note::Regular { .. } | Note::Golden { .. } | Note::Freestyle { .. } => None,
+            Note::Regular { .. }
+            | Note::Golden { .. }
+            | Note::Freestyle { .. }
+            | Note::Rap { .. }
+            | Note::RapGolden { .. }
+            | Note::BpmChange { .. } => None,
         }
     }
 }
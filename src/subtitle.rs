@@ -0,0 +1,192 @@
+use crate::duet::Player;
+use crate::lyrics::LyricLine;
+use crate::structs::TXTSong;
+use crate::timing::TimeConverter;
+
+// the styles of the players in ASS, sung syllables change from white to the color of the player
+const ASS_STYLES: [(Player, &str, &str, u8); 3] = [
+    (Player::One, "P1", "&H00FF9900", 2),
+    (Player::Two, "P2", "&H000099FF", 8),
+    (Player::Both, "Both", "&H0000CC00", 5),
+];
+
+// splits a time in ms into hours, minutes, seconds and ms
+fn split_time(ms: f32) -> (u64, u64, u64, u64) {
+    let ms = ms.max(0.0).round() as u64;
+    (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+// formats a time in ms as a timestamp of the form hh:mm:ss,mmm
+fn srt_timestamp(ms: f32) -> String {
+    let (hours, minutes, seconds, ms) = split_time(ms);
+    format!("{:02}:{:02}:{:02},{:03}", hours, minutes, seconds, ms)
+}
+
+// formats a time in ms as a timestamp of the form hh:mm:ss.mmm
+fn webvtt_timestamp(ms: f32) -> String {
+    let (hours, minutes, seconds, ms) = split_time(ms);
+    format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, ms)
+}
+
+// formats a time in centiseconds as a timestamp of the form h:mm:ss.cc
+fn ass_timestamp(centiseconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centiseconds / 360_000,
+        centiseconds / 6000 % 60,
+        centiseconds / 100 % 60,
+        centiseconds % 100
+    )
+}
+
+// converts a beat of the song into centiseconds
+fn centiseconds_at_beat(converter: &TimeConverter, beat: i32) -> u64 {
+    (converter.ms_at_beat(beat as f32).max(0.0) / 10.0).round() as u64
+}
+
+// returns the lyric lines of the song sorted by their start, the lines of a duet are interleaved
+fn lines_in_time_order(song: &TXTSong) -> Vec<LyricLine> {
+    let mut lines = song.lyric_lines();
+    lines.sort_by_key(LyricLine::start);
+    lines
+}
+
+// escapes the characters that start tags and character references in WebVTT
+fn escape_webvtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// escapes the characters that start override tags in ASS
+fn escape_ass(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('{', "\\{")
+        .replace('}', "\\}")
+}
+
+/// Converts the lyrics of a song to SRT subtitles with a cue for every line
+///
+/// The cues are sorted by their start, so the lines of a duet are numbered in the order
+/// they are sung.
+///
+/// # Arguments
+/// * song - the song to convert
+///
+pub fn generate_srt(song: &TXTSong) -> String {
+    let mut srt = String::new();
+    let converter = song.time_converter();
+    for (i, line) in lines_in_time_order(song).iter().enumerate() {
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            srt_timestamp(converter.ms_at_beat(line.start() as f32)),
+            srt_timestamp(converter.ms_at_beat(line.end() as f32)),
+            line.text()
+        ));
+    }
+    srt
+}
+
+/// Converts the lyrics of a song to WebVTT subtitles with a cue for every line
+///
+/// The cues are sorted by their start. The lines of a duet are marked with voice spans
+/// named after the singers.
+///
+/// # Arguments
+/// * song - the song to convert
+///
+pub fn generate_webvtt(song: &TXTSong) -> String {
+    let mut webvtt = String::from("WEBVTT\n\n");
+    let duet = song.is_duet();
+    let converter = song.time_converter();
+    for line in lines_in_time_order(song).iter() {
+        let text = escape_webvtt(&line.text());
+        let text = if duet {
+            let singer = match line.player {
+                Player::One => song.header.duet_singer_p1.as_deref().unwrap_or("P1"),
+                Player::Two => song.header.duet_singer_p2.as_deref().unwrap_or("P2"),
+                Player::Both => "Both",
+            };
+            format!("<v {}>{}", escape_webvtt(singer), text)
+        } else {
+            text
+        };
+        webvtt.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            webvtt_timestamp(converter.ms_at_beat(line.start() as f32)),
+            webvtt_timestamp(converter.ms_at_beat(line.end() as f32)),
+            text
+        ));
+    }
+    webvtt
+}
+
+/// Converts the lyrics of a song to ASS subtitles with karaoke timing for every syllable
+///
+/// Every line becomes a dialogue event, every syllable is timed with a \k tag.
+/// The players of a duet get their own styles: P1 at the bottom, P2 at the top
+/// and the lines of both players in the middle of the screen.
+///
+/// # Arguments
+/// * song - the song to convert
+///
+pub fn generate_ass(song: &TXTSong) -> String {
+    let mut ass = format!(
+        "[Script Info]\nTitle: {} - {}\nScriptType: v4.00+\n\n",
+        song.header.artist, song.header.title
+    );
+    ass.push_str("[V4+ Styles]\n");
+    ass.push_str(
+        "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+         BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+         BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+    );
+    for &(_, name, colour, alignment) in ASS_STYLES.iter() {
+        ass.push_str(&format!(
+            "Style: {},Arial,48,{},&H00FFFFFF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,{},\
+             10,10,30,1\n",
+            name, colour, alignment
+        ));
+    }
+    ass.push_str("\n[Events]\n");
+    let converter = song.time_converter();
+    ass.push_str(
+        "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    for part in song.line_parts() {
+        let style = ASS_STYLES
+            .iter()
+            .find(|style| style.0 == part.player)
+            .map_or("P1", |style| style.1);
+        let start = centiseconds_at_beat(&converter, part.syllables[0].1);
+        let end = centiseconds_at_beat(&converter, part.syllables[part.syllables.len() - 1].2);
+        let mut text = String::new();
+        for (i, &(syllable, syllable_start, syllable_end)) in part.syllables.iter().enumerate() {
+            // a syllable lasts until the next one starts, rounding is not accumulated
+            let next = match part.syllables.get(i + 1) {
+                Some(&(_, next_start, _)) => centiseconds_at_beat(&converter, next_start),
+                None => centiseconds_at_beat(&converter, syllable_end),
+            };
+            let duration = next.saturating_sub(centiseconds_at_beat(&converter, syllable_start));
+            // continuations are sung, but not shown
+            let shown = match syllable.trim() {
+                "-" => "",
+                _ => syllable,
+            };
+            text.push_str(&format!(
+                "{{\\k{}}}{}",
+                duration,
+                escape_ass(&shown.replace('~', ""))
+            ));
+        }
+        ass.push_str(&format!(
+            "Dialogue: 0,{},{},{},,0,0,0,,{}\n",
+            ass_timestamp(start),
+            ass_timestamp(end),
+            style,
+            text.trim_end()
+        ));
+    }
+    ass
+}
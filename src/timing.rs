@@ -0,0 +1,171 @@
+use crate::structs::{Note, TXTSong};
+
+// returns the duration of a single beat in ms, the bpm of the file are quarter beats
+pub(crate) fn beat_duration_ms(bpm: f32) -> f32 {
+    60_000.0 / (bpm * 4.0)
+}
+
+/// Converts between the beats of a song and the time in ms from the beginning of the audio file
+///
+/// The converter keeps the gap and the tempo map of the song it was created from,
+/// later changes of the song are not seen by it.
+#[derive(PartialEq, Clone, Debug)]
+pub struct TimeConverter {
+    gap_ms: f32,
+    bpm: f32,
+    tempo_map: Vec<(i32, f32)>,
+}
+
+impl TimeConverter {
+    /// Converts a beat into the time in ms from the beginning of the audio file
+    ///
+    /// # Arguments
+    /// * beat - the absolute beat, may be fractional
+    ///
+    pub fn ms_at_beat(&self, beat: f32) -> f32 {
+        let tempo_map = &self.tempo_map;
+        let mut ms = self.gap_ms;
+        for (i, &(change_beat, bpm)) in tempo_map.iter().enumerate() {
+            let segment_start = if i == 0 {
+                f32::min(0.0, beat)
+            } else {
+                change_beat as f32
+            };
+            if beat <= segment_start {
+                break;
+            }
+            let segment_end = match tempo_map.get(i + 1) {
+                Some(&(next_beat, _)) => f32::min(next_beat as f32, beat),
+                None => beat,
+            };
+            ms += (segment_end - segment_start) * beat_duration_ms(bpm);
+        }
+        // beats before the beginning of the song use the bpm of the header
+        if beat < 0.0 {
+            ms += beat * beat_duration_ms(self.bpm);
+        }
+        ms
+    }
+
+    /// Converts a time in ms from the beginning of the audio file into a beat
+    ///
+    /// # Arguments
+    /// * ms - the time in ms
+    ///
+    pub fn beat_at_ms(&self, ms: f32) -> f32 {
+        let tempo_map = &self.tempo_map;
+        let mut segment_ms = self.gap_ms;
+        if ms < segment_ms {
+            return (ms - segment_ms) / beat_duration_ms(self.bpm);
+        }
+        for (i, &(change_beat, bpm)) in tempo_map.iter().enumerate() {
+            let duration = beat_duration_ms(bpm);
+            if let Some(&(next_beat, _)) = tempo_map.get(i + 1) {
+                let next_ms = segment_ms + (next_beat - change_beat) as f32 * duration;
+                if ms >= next_ms {
+                    segment_ms = next_ms;
+                    continue;
+                }
+            }
+            return change_beat as f32 + (ms - segment_ms) / duration;
+        }
+        unreachable!("the tempo map always contains the bpm of the header")
+    }
+}
+
+impl TXTSong {
+    /// returns the gap of the song in ms, 0 if the song has no gap
+    pub fn gap_ms(&self) -> f32 {
+        self.header.gap.unwrap_or(0.0)
+    }
+
+    /// Returns the beat that is the origin of the notes of a line
+    ///
+    /// For songs with absolute timing this is always 0, for songs with relative timing
+    /// it is the sum of the shifts of all line breaks up to and including this line.
+    ///
+    /// # Arguments
+    /// * line_index - the index of the line in the lines of the song
+    ///
+    pub fn line_offset(&self, line_index: usize) -> i32 {
+        if !self.header.relative.unwrap_or(false) {
+            return 0;
+        }
+        self.lines
+            .iter()
+            .take(line_index + 1)
+            .map(|line| line.rel.unwrap_or(0))
+            .sum()
+    }
+
+    /// returns a converter between beats and time that reads the tempo map of the song once
+    pub fn time_converter(&self) -> TimeConverter {
+        TimeConverter {
+            gap_ms: self.gap_ms(),
+            bpm: self.header.bpm,
+            tempo_map: self.tempo_map(),
+        }
+    }
+
+    /// Converts a beat into the time in ms from the beginning of the audio file
+    ///
+    /// The conversion respects the gap and all bpm changes of the song.
+    /// Use `time_converter` to convert many beats of the same song.
+    ///
+    /// # Arguments
+    /// * beat - the absolute beat, may be fractional
+    ///
+    pub fn ms_at_beat(&self, beat: f32) -> f32 {
+        self.time_converter().ms_at_beat(beat)
+    }
+
+    /// Converts a time in ms from the beginning of the audio file into a beat
+    ///
+    /// This is the inverse of `ms_at_beat`.
+    /// Use `time_converter` to convert many times of the same song.
+    ///
+    /// # Arguments
+    /// * ms - the time in ms
+    ///
+    pub fn beat_at_ms(&self, ms: f32) -> f32 {
+        self.time_converter().beat_at_ms(ms)
+    }
+
+    /// Returns the start and the end of a note in ms from the beginning of the audio file
+    ///
+    /// Returns None for notes without a duration like player changes and bpm changes.
+    ///
+    /// # Arguments
+    /// * line_index - the index of the line the note belongs to
+    /// * note - the note, usually taken from that line
+    ///
+    pub fn note_time_ms(&self, line_index: usize, note: &Note) -> Option<(f32, f32)> {
+        let offset = self.line_offset(line_index);
+        let start = offset + note.start()?;
+        let end = start + note.duration()?;
+        let converter = self.time_converter();
+        Some((
+            converter.ms_at_beat(start as f32),
+            converter.ms_at_beat(end as f32),
+        ))
+    }
+
+    /// Returns the part of the audio file that is played, in ms
+    ///
+    /// The range starts at the START tag or 0 and ends at the END tag if the song has one.
+    pub fn playback_range_ms(&self) -> (f32, Option<f32>) {
+        let start = self.header.start.map_or(0.0, |x| x * 1000.0);
+        let end = self.header.end.map(|x| x as f32);
+        (start, end)
+    }
+
+    /// Returns true if a time in ms lies inside the part of the audio file that is played
+    ///
+    /// # Arguments
+    /// * ms - the time in ms
+    ///
+    pub fn is_played_at_ms(&self, ms: f32) -> bool {
+        let (start, end) = self.playback_range_ms();
+        ms >= start && end.is_none_or(|end| ms <= end)
+    }
+}
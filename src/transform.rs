@@ -0,0 +1,315 @@
+use crate::duet::Player;
+use crate::structs::{Note, TXTSong};
+use crate::timing::beat_duration_ms;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Describes the rounding loss of a tempo rescale
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct RescaleReport {
+    /// the factor every beat was multiplied with, NaN if the song was not rescaled
+    pub factor: f32,
+    /// the number of beats and durations that had to be rounded
+    pub rounded_values: usize,
+    /// the largest rounding error in beats of the new bpm
+    pub max_error_beats: f32,
+    /// the largest rounding error in ms, at the bpm of the header
+    pub max_error_ms: f32,
+}
+
+impl RescaleReport {
+    /// returns true if the song was rescaled and every position was kept exactly
+    pub fn is_lossless(&self) -> bool {
+        !self.factor.is_nan() && self.rounded_values == 0
+    }
+
+    // scales a beat, durations keep at least one beat
+    fn scale(&mut self, value: &mut i32, min: Option<i32>) {
+        let exact = *value as f32 * self.factor;
+        let mut rounded = exact.round() as i32;
+        if let Some(min) = min {
+            rounded = rounded.max(min);
+        }
+        let error = (rounded as f32 - exact).abs();
+        if error > 1e-3 {
+            self.rounded_values += 1;
+            self.max_error_beats = self.max_error_beats.max(error);
+        }
+        *value = rounded;
+    }
+}
+
+// moves a note by the given number of beats
+fn shift_note(note: &mut Note, beats: i32) {
+    if let Note::BpmChange { ref mut beat, .. } = *note {
+        *beat += beats;
+    } else if let Some(start) = note.start_mut() {
+        *start += beats;
+    }
+}
+
+// changes the pitch of a note by the given number of semitones
+fn transpose_note(note: &mut Note, semitones: i32) {
+    if let Some(pitch) = note.pitch_mut() {
+        *pitch += semitones;
+    }
+}
+
+impl TXTSong {
+    /// Returns the song with absolute timing
+    ///
+    /// The notes and line breaks of a song with relative timing are moved to their absolute beats
+    /// and the RELATIVE tag is removed. A song with absolute timing is returned unchanged.
+    pub fn to_absolute(&self) -> TXTSong {
+        let mut song = self.clone();
+        if !self.header.relative.unwrap_or(false) {
+            return song;
+        }
+        let mut offset = 0;
+        for line in song.lines.iter_mut() {
+            // the line break is relative to the origin of the previous line
+            line.start += offset;
+            offset += line.rel.take().unwrap_or(0);
+            for note in line.notes.iter_mut() {
+                shift_note(note, offset);
+            }
+        }
+        song.header.relative = None;
+        song
+    }
+
+    /// Returns the song with relative timing
+    ///
+    /// Every line after the first one gets its own origin at the start of its first note,
+    /// or at its line break if it has no notes. A song with relative timing is returned unchanged.
+    pub fn to_relative(&self) -> TXTSong {
+        if self.header.relative.unwrap_or(false) {
+            return self.clone();
+        }
+        let mut song = self.clone();
+        let mut offset = 0;
+        for (i, line) in song.lines.iter_mut().enumerate() {
+            if i > 0 {
+                let origin = line
+                    .notes
+                    .iter()
+                    .find_map(|note| note.start())
+                    .unwrap_or(line.start);
+                line.start -= offset;
+                line.rel = Some(origin - offset);
+                offset = origin;
+            }
+            for note in line.notes.iter_mut() {
+                shift_note(note, -offset);
+            }
+        }
+        song.header.relative = Some(true);
+        song
+    }
+
+    /// Transposes all notes of the song by the given number of semitones
+    ///
+    /// # Arguments
+    /// * semitones - the number of semitones, negative values transpose down
+    ///
+    pub fn transpose(&mut self, semitones: i32) {
+        for note in self.lines.iter_mut().flat_map(|line| line.notes.iter_mut()) {
+            transpose_note(note, semitones);
+        }
+    }
+
+    /// Transposes the notes of a single player of a duet by the given number of semitones
+    ///
+    /// Notes before the first player change belong to the first player.
+    ///
+    /// # Arguments
+    /// * player - the player whose notes are transposed
+    /// * semitones - the number of semitones, negative values transpose down
+    ///
+    pub fn transpose_player(&mut self, player: Player, semitones: i32) {
+        let mut current_player = Player::One;
+        for note in self.lines.iter_mut().flat_map(|line| line.notes.iter_mut()) {
+            if let Note::PlayerChange { player: number } = *note {
+                current_player = Player::from_number(number).unwrap_or(current_player);
+            } else if current_player == player {
+                transpose_note(note, semitones);
+            }
+        }
+    }
+
+    /// returns the median pitch of the sung notes, None if the song has no sung notes
+    pub fn median_pitch(&self) -> Option<i32> {
+        let mut pitches: Vec<i32> = self
+            .lines
+            .iter()
+            .flat_map(|line| line.notes.iter())
+            .filter(|note| matches!(note, Note::Regular { .. } | Note::Golden { .. }))
+            .filter_map(|note| note.pitch())
+            .collect();
+        if pitches.is_empty() {
+            return None;
+        }
+        pitches.sort_unstable();
+        Some(pitches[(pitches.len() - 1) / 2])
+    }
+
+    /// Transposes the song by whole octaves so its median pitch lies in the given octave
+    ///
+    /// Returns the number of semitones the song was transposed by.
+    /// The octaves are named like the pitches of the notes with pitch 0 being C2,
+    /// so middle C (pitch 12, MIDI note 60) is C3 and not C4 as in scientific pitch notation.
+    ///
+    /// # Arguments
+    /// * octave - the octave of the median pitch, 3 is the octave of middle C
+    ///
+    pub fn normalize_octave(&mut self, octave: i32) -> i32 {
+        let median = match self.median_pitch() {
+            Some(x) => x,
+            None => return 0,
+        };
+        // pitch 0 is C2
+        let octave_start = (octave - 2) * 12;
+        let semitones = -(median - octave_start).div_euclid(12) * 12;
+        self.transpose(semitones);
+        semitones
+    }
+
+    /// Changes the bpm of the song and rescales all beats so the timing in ms is kept
+    ///
+    /// The starts and durations of the notes, the line breaks, bpm changes and medley beats
+    /// are multiplied by the ratio of the new and the old bpm and rounded to whole beats.
+    /// Durations keep at least one beat. The returned report describes the rounding loss.
+    /// If the new or the old bpm is not a finite number greater than 0 the song is not changed
+    /// and the report is not lossless.
+    ///
+    /// # Arguments
+    /// * new_bpm - the new bpm of the header
+    ///
+    pub fn rescale_bpm(&mut self, new_bpm: f32) -> RescaleReport {
+        let valid = |bpm: f32| bpm.is_finite() && bpm > 0.0;
+        if !valid(new_bpm) || !valid(self.header.bpm) {
+            return RescaleReport {
+                factor: f32::NAN,
+                ..RescaleReport::default()
+            };
+        }
+        let mut report = RescaleReport {
+            factor: new_bpm / self.header.bpm,
+            ..RescaleReport::default()
+        };
+        for line in self.lines.iter_mut() {
+            report.scale(&mut line.start, None);
+            if let Some(ref mut rel) = line.rel {
+                report.scale(rel, None);
+            }
+            for note in line.notes.iter_mut() {
+                if let Note::BpmChange {
+                    ref mut beat,
+                    ref mut bpm,
+                } = *note
+                {
+                    report.scale(beat, None);
+                    *bpm *= report.factor;
+                }
+                if let Some(start) = note.start_mut() {
+                    report.scale(start, None);
+                }
+                if let Some(duration) = note.duration_mut() {
+                    let min = if *duration > 0 { Some(1) } else { None };
+                    report.scale(duration, min);
+                }
+            }
+        }
+        if let Some(ref mut beat) = self.header.medley_start_beat {
+            report.scale(beat, None);
+        }
+        if let Some(ref mut beat) = self.header.medley_end_beat {
+            report.scale(beat, None);
+        }
+        self.header.bpm = new_bpm;
+        report.max_error_ms = report.max_error_beats * beat_duration_ms(new_bpm);
+        report
+    }
+
+    /// Moves the whole song by changing its gap, the notes keep their beats
+    ///
+    /// # Arguments
+    /// * ms - the offset in ms, negative values move the song to the beginning of the audio
+    ///
+    pub fn shift_gap(&mut self, ms: f32) {
+        self.header.gap = Some(self.gap_ms() + ms);
+    }
+
+    /// Moves all notes, line breaks and bpm changes of the song by the given number of beats
+    ///
+    /// # Arguments
+    /// * beats - the offset in beats, negative values move the notes to the beginning
+    ///
+    pub fn shift_beats(&mut self, beats: i32) {
+        self.shift_from_line(0, beats);
+    }
+
+    /// Moves the notes of a line and all following lines by the given number of beats
+    ///
+    /// The line break in front of the line is moved as well, the lines before stay unchanged.
+    /// The medley beats are moved if they lie in the moved part of the song.
+    ///
+    /// # Arguments
+    /// * line_index - the index of the first line that is moved
+    /// * beats - the offset in beats, negative values move the notes to the beginning
+    ///
+    pub fn shift_from_line(&mut self, line_index: usize, beats: i32) {
+        if line_index >= self.lines.len() {
+            return;
+        }
+        let first_beat = match line_index {
+            0 => i32::MIN,
+            _ => self.line_offset(line_index - 1) + self.lines[line_index].start,
+        };
+        let relative = self.header.relative.unwrap_or(false);
+        for (i, line) in self.lines.iter_mut().enumerate().skip(line_index) {
+            if i > 0 {
+                line.start += beats;
+            }
+            // in relative timing moving the origin of a line moves all following lines
+            if let (true, Some(rel)) = (relative, line.rel.as_mut()) {
+                *rel += beats;
+                break;
+            }
+            for note in line.notes.iter_mut() {
+                shift_note(note, beats);
+            }
+        }
+        let header = &mut self.header;
+        for beat in [&mut header.medley_start_beat, &mut header.medley_end_beat] {
+            match beat {
+                Some(beat) if *beat >= first_beat => *beat += beats,
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves the notes of a line and all following lines by an offset in ms
+    ///
+    /// The offset is converted into whole beats at the tempo of the line.
+    /// Returns the number of beats the notes were moved by.
+    ///
+    /// # Arguments
+    /// * line_index - the index of the first line that is moved
+    /// * ms - the offset in ms, negative values move the notes to the beginning
+    ///
+    pub fn shift_from_line_ms(&mut self, line_index: usize, ms: f32) -> i32 {
+        let line = match self.lines.get(line_index) {
+            Some(x) => x,
+            None => return 0,
+        };
+        let first_beat = line.notes.iter().find_map(|note| note.start()).unwrap_or(0)
+            + self.line_offset(line_index);
+        let converter = self.time_converter();
+        let beat = converter.beat_at_ms(converter.ms_at_beat(first_beat as f32) + ms);
+        let beats = (beat - first_beat as f32).round() as i32;
+        self.shift_from_line(line_index, beats);
+        beats
+    }
+}
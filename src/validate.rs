@@ -0,0 +1,278 @@
+use crate::duet::Player;
+use crate::structs::{Note, Severity, TXTSong};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// the lowest pitch that is expected in a song (C1)
+pub const MIN_PITCH: i32 = -12;
+/// the highest pitch that is expected in a song (C8)
+pub const MAX_PITCH: i32 = 72;
+
+/// Describes the problems the validation finds in a song
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum LintKind {
+    /// a note starts before the previous note of the same player ends
+    OverlappingNotes,
+    /// a note starts before the previous note of the same player
+    NotesOutOfOrder,
+    /// a line break is placed after the start of the first note of its line
+    LineBreakAfterNote,
+    /// a line break is placed before the end of the last note of the previous line
+    LineBreakBeforeNoteEnd,
+    /// a note has a duration of zero or less
+    ZeroLengthNote,
+    /// the pitch of a note is outside of the range a human can sing
+    PitchOutOfRange(i32),
+    /// a note starts before the gap, at a negative beat
+    NoteBeforeGap,
+    /// a line contains no notes that can be sung
+    EmptyLine,
+    /// the text of a note ends with whitespace that does not separate words
+    TrailingWhitespace,
+    /// a note continues the previous syllable with "-" or a padded "~" instead of "~"
+    NonStandardContinuation,
+}
+
+impl LintKind {
+    /// Returns the code of the lint
+    ///
+    /// The codes are stable and can be used to filter or suppress lints.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            LintKind::OverlappingNotes => "overlapping-notes",
+            LintKind::NotesOutOfOrder => "notes-out-of-order",
+            LintKind::LineBreakAfterNote => "line-break-after-note",
+            LintKind::LineBreakBeforeNoteEnd => "line-break-before-note-end",
+            LintKind::ZeroLengthNote => "zero-length-note",
+            LintKind::PitchOutOfRange(_) => "pitch-out-of-range",
+            LintKind::NoteBeforeGap => "note-before-gap",
+            LintKind::EmptyLine => "empty-line",
+            LintKind::TrailingWhitespace => "trailing-whitespace",
+            LintKind::NonStandardContinuation => "non-standard-continuation",
+        }
+    }
+
+    /// returns the default severity of the lint
+    pub fn severity(&self) -> Severity {
+        match *self {
+            LintKind::OverlappingNotes
+            | LintKind::NotesOutOfOrder
+            | LintKind::LineBreakAfterNote
+            | LintKind::ZeroLengthNote => Severity::Error,
+            LintKind::LineBreakBeforeNoteEnd
+            | LintKind::PitchOutOfRange(_)
+            | LintKind::NoteBeforeGap
+            | LintKind::EmptyLine
+            | LintKind::TrailingWhitespace
+            | LintKind::NonStandardContinuation => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LintKind::OverlappingNotes => write!(f, "note overlaps the previous note"),
+            LintKind::NotesOutOfOrder => write!(f, "note starts before the previous note"),
+            LintKind::LineBreakAfterNote => {
+                write!(f, "line break is after the start of the next note")
+            }
+            LintKind::LineBreakBeforeNoteEnd => {
+                write!(f, "line break is before the end of the previous note")
+            }
+            LintKind::ZeroLengthNote => write!(f, "note has no duration"),
+            LintKind::PitchOutOfRange(pitch) => write!(f, "pitch {} is out of range", pitch),
+            LintKind::NoteBeforeGap => write!(f, "note starts before the gap"),
+            LintKind::EmptyLine => write!(f, "line contains no notes"),
+            LintKind::TrailingWhitespace => write!(f, "text ends with superfluous whitespace"),
+            LintKind::NonStandardContinuation => write!(f, "continuation is not written as \"~\""),
+        }
+    }
+}
+
+/// Describes a problem found in a song and where it was found
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Lint {
+    /// the kind of the problem
+    pub kind: LintKind,
+    /// the severity of the problem
+    pub severity: Severity,
+    /// the index of the line in the lines of the song
+    pub line: usize,
+    /// the index of the note in the notes of the line, None if the whole line is affected
+    pub note: Option<usize>,
+}
+
+impl Lint {
+    fn new(kind: LintKind, line: usize, note: Option<usize>) -> Self {
+        Lint {
+            severity: kind.severity(),
+            kind,
+            line,
+            note,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] line {}", self.kind.code(), self.line)?;
+        if let Some(note) = self.note {
+            write!(f, ", note {}", note)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+// a note that is sung, with its location, its player and its absolute beats
+pub(crate) struct SungNote {
+    pub(crate) line: usize,
+    pub(crate) note: usize,
+    pub(crate) player: Player,
+    pub(crate) start: i32,
+    pub(crate) end: i32,
+}
+
+// returns the notes of the song that are sung, in the order of the song
+pub(crate) fn sung_notes(song: &TXTSong) -> Vec<SungNote> {
+    let mut notes = Vec::new();
+    let mut player = Player::One;
+    for (line_index, line) in song.lines.iter().enumerate() {
+        let offset = song.line_offset(line_index);
+        for (note_index, note) in line.notes.iter().enumerate() {
+            if let Note::PlayerChange { player: number } = *note {
+                player = Player::from_number(number).unwrap_or(player);
+            } else if let (Some(start), Some(duration)) = (note.start(), note.duration()) {
+                notes.push(SungNote {
+                    line: line_index,
+                    note: note_index,
+                    player,
+                    start: offset + start,
+                    end: offset + start + duration,
+                });
+            }
+        }
+    }
+    notes
+}
+
+// returns the index of the previous note of the same player for every sung note
+pub(crate) fn previous_notes(notes: &[SungNote]) -> Vec<Option<usize>> {
+    let mut last_notes = [None; 3];
+    notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| last_notes[note.player.number() as usize - 1].replace(i))
+        .collect()
+}
+
+// returns the text a note should have: a continuation is written as "~"
+// and only a single space may follow a syllable that is not the last of its line
+pub(crate) fn normalized_text(text: &str, last_in_line: bool) -> String {
+    let trimmed = text.trim_end();
+    let ending = if last_in_line || trimmed.len() == text.len() {
+        ""
+    } else {
+        " "
+    };
+    // old songs use "-" instead of "~" to continue the previous syllable
+    match trimmed.trim_start() {
+        "~" | "-" => format!("~{}", ending),
+        _ => format!("{}{}", trimmed, ending),
+    }
+}
+
+// returns the lint for the text of a note that is not normalized
+fn text_lint(text: &str, last_in_line: bool) -> Option<LintKind> {
+    if normalized_text(text, last_in_line) == text {
+        return None;
+    }
+    let trimmed = text.trim_end();
+    if matches!(trimmed.trim_start(), "~" | "-") && trimmed != "~" {
+        Some(LintKind::NonStandardContinuation)
+    } else {
+        Some(LintKind::TrailingWhitespace)
+    }
+}
+
+/// Checks a song for problems the parser does not detect
+///
+/// The lints are sorted by their location in the song.
+/// Notes of different players of a duet are checked separately.
+///
+/// # Arguments
+/// * song - the song to check
+///
+pub fn validate(song: &TXTSong) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let notes = sung_notes(song);
+    let previous = previous_notes(&notes);
+    let mut line_end = 0;
+    for (line_index, line) in song.lines.iter().enumerate() {
+        let line_start = line_end;
+        while line_end < notes.len() && notes[line_end].line == line_index {
+            line_end += 1;
+        }
+        let line_notes = &notes[line_start..line_end];
+
+        if let Some(first_note) = line_notes.first().filter(|_| line_index > 0) {
+            // the line break is relative to the origin of the previous line
+            let line_break = song.line_offset(line_index - 1) + line.start;
+            let previous_end = notes[..line_start]
+                .last()
+                .filter(|note| note.line + 1 == line_index)
+                .map(|note| note.end);
+            if line_break > first_note.start {
+                lints.push(Lint::new(
+                    LintKind::LineBreakAfterNote,
+                    line_index,
+                    Some(first_note.note),
+                ));
+            } else if previous_end.is_some_and(|end| line_break < end.min(first_note.start)) {
+                lints.push(Lint::new(
+                    LintKind::LineBreakBeforeNoteEnd,
+                    line_index,
+                    None,
+                ));
+            }
+        }
+
+        for (i, sung_note) in line_notes.iter().enumerate() {
+            let note = &line.notes[sung_note.note];
+            let mut lint = |kind| lints.push(Lint::new(kind, line_index, Some(sung_note.note)));
+            if let Some(previous_note) = previous[line_start + i].map(|j| &notes[j]) {
+                if sung_note.start < previous_note.start {
+                    lint(LintKind::NotesOutOfOrder);
+                } else if sung_note.start < previous_note.end {
+                    lint(LintKind::OverlappingNotes);
+                }
+            }
+            if sung_note.end <= sung_note.start {
+                lint(LintKind::ZeroLengthNote);
+            }
+            if let Some(pitch) = note.pitch() {
+                // the pitch of freestyle and rap notes is not sung
+                let sung = matches!(note, Note::Regular { .. } | Note::Golden { .. });
+                if sung && !(MIN_PITCH..=MAX_PITCH).contains(&pitch) {
+                    lint(LintKind::PitchOutOfRange(pitch));
+                }
+            }
+            if sung_note.start < 0 {
+                lint(LintKind::NoteBeforeGap);
+            }
+            if let Some(kind) = note
+                .text()
+                .and_then(|text| text_lint(text, i + 1 == line_notes.len()))
+            {
+                lint(kind);
+            }
+        }
+        if line_notes.is_empty() {
+            lints.push(Lint::new(LintKind::EmptyLine, line_index, None));
+        }
+    }
+    lints
+}
@@ -356,6 +356,1251 @@ fn remote_url_audio() {
                Source::Remote(Url::parse("https://www.example.com/Testfile.mp3").unwrap()));
 }
 
+#[test]
+fn variable_bpm() {
+    let txt = include_str!("txts/variable_bpm.txt");
+    let lines = parse_txt_lines_str(txt).unwrap();
+    assert_eq!(
+        lines[1].notes[0],
+        Note::BpmChange {
+            beat: 22,
+            bpm: 246.5
+        }
+    );
+}
+
+#[test]
+fn generate_and_reparse_variable_bpm() {
+    let txt = include_str!("txts/variable_bpm.txt");
+    let header = parse_txt_header_str(txt).unwrap();
+    let lines = parse_txt_lines_str(txt).unwrap();
+
+    let generated_txt = generate_song_txt(&header, &lines).unwrap();
+    assert_eq!(parse_txt_lines_str(generated_txt.as_ref()).unwrap(), lines);
+}
+
+#[test]
+fn tempo_map_with_relative_timing() {
+    let txt = include_str!("txts/relative_variable_bpm.txt");
+    let song = TXTSong {
+        header: parse_txt_header_str(txt).unwrap(),
+        lines: parse_txt_lines_str(txt).unwrap(),
+    };
+    assert_eq!(song.tempo_map(), vec![(0, 123.0), (26, 246.0)]);
+}
+
+#[test]
+fn rap_notes() {
+    let txt = include_str!("txts/rap_notes.txt");
+    let lines = parse_txt_lines_str(txt).unwrap();
+    assert_eq!(
+        lines[0].notes[2],
+        Note::Rap {
+            start: 8,
+            duration: 4,
+            pitch: 0,
+            text: String::from("'m "),
+        }
+    );
+    assert_eq!(lines[0].notes[3].text(), Some("test"));
+
+    let header = parse_txt_header_str(txt).unwrap();
+    let generated_txt = generate_song_txt(&header, &lines).unwrap();
+    assert_eq!(parse_txt_lines_str(generated_txt.as_ref()).unwrap(), lines);
+}
+
+#[test]
+fn version_audio_tag() {
+    let txt = include_str!("txts/version_audio_tag.txt");
+    let mut header = get_simple_txt_header();
+    header.version = Version::V1_1_0;
+    header.audio_path = Source::parse("Testfile.ogg");
+    header.relative = None;
+    let mut unknown = HashMap::new();
+    unknown.insert(String::from("MP3"), String::from("Testfile.mp3"));
+    header.unknown = Some(unknown);
+    assert_eq!(parse_txt_header_str(txt).unwrap(), header);
+}
+
+#[test]
+fn mp3_fallback_in_versioned_file() {
+    let song = parse_txt_song_str(include_str!("txts/version_audio_tag.txt")).unwrap();
+    let generated_txt = generate_song_txt(&song.header, &song.lines).unwrap();
+    assert!(generated_txt.contains("#AUDIO:Testfile.ogg\n#MP3:Testfile.mp3\n"));
+    assert_eq!(parse_txt_song_str(&generated_txt).unwrap(), song);
+
+    let legacy_txt = generate_song_txt_for_version(&song.header, &song.lines, Version::Legacy);
+    let legacy_txt = legacy_txt.unwrap();
+    assert!(legacy_txt.contains("#MP3:Testfile.ogg\n"));
+    assert!(!legacy_txt.contains("Testfile.mp3"));
+}
+
+#[test]
+fn unsupported_version() {
+    assert_eq!(Version::parse("0.9"), None);
+    assert_eq!(Version::parse("1.1.5"), Some(Version::V1_1_0));
+    let txt = "#VERSION:0.9\n#TITLE:Test\n#ARTIST:Test\n#MP3:Test.mp3\n#BPM:100\n: 0 1 0 a\nE";
+    assert_error_kind!(
+        parse_txt_song_str(txt).unwrap_err(),
+        ultrastar_txt::parser::ErrorKind::ValueError(1, "VERSION")
+    );
+}
+
+#[test]
+fn audio_tag_in_legacy_file() {
+    let txt = include_str!("txts/audio_tag_in_legacy_file.txt");
+    let header = parse_txt_header_str(txt).unwrap();
+    assert_eq!(header.audio_path, Source::parse("Testfile.mp3"));
+    assert_eq!(
+        header.unknown.unwrap().get("AUDIO"),
+        Some(&String::from("Testfile.ogg"))
+    );
+}
+
+#[test]
+fn relative_in_versioned_file() {
+    let txt = include_str!("txts/relative_in_versioned_file.txt");
+    assert_error_kind!(
+        parse_txt_header_str(txt).err().unwrap(),
+        ultrastar_txt::parser::ErrorKind::UnsupportedTag(8, "RELATIVE")
+    );
+}
+
+#[test]
+fn value_error_in_header_version() {
+    let txt = include_str!("txts/value_error_in_header_version.txt");
+    assert_error_kind!(
+        parse_txt_header_str(txt).err().unwrap(),
+        ultrastar_txt::parser::ErrorKind::ValueError(1, "VERSION")
+    );
+}
+
+#[test]
+fn generate_song_for_version() {
+    let mut header = get_simple_txt_header();
+    let lines = get_simple_txt_lines();
+    header.relative = None;
+
+    let generated_txt = generate_song_txt_for_version(&header, &lines, Version::V1_2_0).unwrap();
+    assert!(generated_txt.starts_with("#VERSION:1.2.0\n"));
+    assert!(generated_txt.contains("#AUDIO:Testfile.mp3\n"));
+    let parsed_header = parse_txt_header_str(generated_txt.as_ref()).unwrap();
+    assert_eq!(parsed_header.version, Version::V1_2_0);
+    assert_eq!(parsed_header.audio_path, header.audio_path);
+
+    header.relative = Some(true);
+    assert_error_kind!(
+        generate_song_txt_for_version(&header, &lines, Version::V1_0_0)
+            .err()
+            .unwrap(),
+        ultrastar_txt::generator::ErrorKind::UnsupportedTag("RELATIVE")
+    );
+}
+
+#[test]
+fn usdx_tags() {
+    let txt = include_str!("txts/usdx_tags.txt");
+    let mut header = get_simple_txt_header();
+    header.creator = Some(String::from("Testcreator"));
+    header.start = Some(1.5);
+    header.end = Some(90000);
+    header.resolution = Some(4);
+    header.notes_gap = Some(0);
+    header.encoding = Some(String::from("UTF8"));
+    header.preview_start = Some(12.25);
+    header.medley_start_beat = Some(550);
+    header.medley_end_beat = Some(863);
+    header.calc_medley = Some(false);
+    header.duet_singer_p1 = Some(String::from("Singer 1"));
+    header.duet_singer_p2 = Some(String::from("Singer 2"));
+    assert_eq!(parse_txt_header_str(txt).unwrap(), header);
+
+    let generated_txt = generate_song_txt(&header, &get_simple_txt_lines()).unwrap();
+    assert_eq!(
+        parse_txt_header_str(generated_txt.as_ref()).unwrap(),
+        header
+    );
+}
+
+#[test]
+fn duplicate_header_duet_singer() {
+    let txt = include_str!("txts/duplicate_header_duet_singer.txt");
+    assert_error_kind!(
+        parse_txt_header_str(txt).err().unwrap(),
+        ultrastar_txt::parser::ErrorKind::DuplicateHeader(7, "DUETSINGERP1")
+    );
+}
+
+#[test]
+fn value_error_in_header_calc_medley() {
+    let txt = include_str!("txts/value_error_in_header_calc_medley.txt");
+    assert_error_kind!(
+        parse_txt_header_str(txt).err().unwrap(),
+        ultrastar_txt::parser::ErrorKind::ValueError(6, "CALCMEDLEY")
+    );
+}
+
+#[test]
+fn encoding_in_versioned_file() {
+    let txt = include_str!("txts/encoding_in_versioned_file.txt");
+    assert_error_kind!(
+        parse_txt_header_str(txt).err().unwrap(),
+        ultrastar_txt::parser::ErrorKind::UnsupportedTag(7, "ENCODING")
+    );
+}
+
+#[test]
+fn lenient_parsing_recovers() {
+    let txt = include_str!("txts/lenient.txt");
+    let (song, diagnostics) = parse_txt_song_str_lenient(txt);
+    assert_eq!(song.header.title, "Testsong");
+    assert_eq!(song.header.gap, None);
+    assert_eq!(song.lines.len(), 2);
+    assert_eq!(song.lines[0].notes.len(), 4);
+    assert_eq!(song.lines[1].notes.len(), 2);
+
+    assert_eq!(diagnostics.len(), 5);
+    assert!(matches!(
+        diagnostics[0].kind,
+        ultrastar_txt::parser::ErrorKind::DuplicateHeader(3, "TITLE")
+    ));
+    assert!(matches!(
+        diagnostics[1].kind,
+        ultrastar_txt::parser::ErrorKind::EmptyHeader(5, _)
+    ));
+    assert_eq!(diagnostics[1].severity, Severity::Warning);
+    assert!(matches!(
+        diagnostics[2].kind,
+        ultrastar_txt::parser::ErrorKind::UnknownNoteType(9)
+    ));
+    assert_eq!(diagnostics[2].severity, Severity::Error);
+    assert_eq!(diagnostics[3].line, Some(14));
+    assert!(matches!(
+        diagnostics[4].kind,
+        ultrastar_txt::parser::ErrorKind::MissingEndIndicator
+    ));
+}
+
+#[test]
+fn lenient_parsing_keeps_song_with_value_error() {
+    let txt = include_str!("txts/value_error_in_header_bpm.txt");
+    let (song, diagnostics) = parse_txt_song_str_lenient(txt);
+    assert_eq!(song.header.title, "Testsong");
+    assert_eq!(song.lines.len(), 1);
+    assert!(matches!(
+        diagnostics[0].kind,
+        ultrastar_txt::parser::ErrorKind::ValueError(5, "BPM")
+    ));
+    assert!(matches!(
+        diagnostics[1].kind,
+        ultrastar_txt::parser::ErrorKind::MissingEssential
+    ));
+}
+
+#[test]
+fn simple_txt_song() {
+    let txt = get_simple_txt_str();
+    let song = TXTSong {
+        header: get_simple_txt_header(),
+        lines: get_simple_txt_lines(),
+    };
+    assert_eq!(song, parse_txt_song_str(txt).unwrap());
+}
+
+#[test]
+fn header_after_notes() {
+    let txt = include_str!("txts/header_after_notes.txt");
+    assert!(parse_txt_header_str(txt).is_ok());
+    assert_error_kind!(
+        parse_txt_song_str(txt).err().unwrap(),
+        ultrastar_txt::parser::ErrorKind::MisplacedHeader(8)
+    );
+}
+
+#[test]
+fn comment_lines() {
+    let txt = include_str!("txts/header_comment.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    assert_eq!(song.header.bpm, 123.0);
+    assert_eq!(song.lines[0].notes.len(), 3);
+    assert_eq!(song, parse_txt_song_reader(txt.as_bytes()).unwrap());
+}
+
+#[test]
+fn song_from_reader() {
+    let txt = get_simple_txt_str();
+    let song = TXTSong {
+        header: get_simple_txt_header(),
+        lines: get_simple_txt_lines(),
+    };
+    assert_eq!(song, parse_txt_song_reader(txt.as_bytes()).unwrap());
+}
+
+#[test]
+fn song_from_reader_detects_encoding() {
+    let txt: &[u8] = include_bytes!("txts/windows_1252.txt");
+    let song = parse_txt_song_reader(txt).unwrap();
+    assert_eq!(song.header.title, "Über die Brücke");
+}
+
+#[test]
+fn write_song_to_writer() {
+    let header = get_simple_txt_header();
+    let lines = get_simple_txt_lines();
+
+    let mut written_txt = Vec::new();
+    write_song_txt(&mut written_txt, &header, &lines).unwrap();
+    assert_eq!(
+        String::from_utf8(written_txt).unwrap(),
+        generate_song_txt(&header, &lines).unwrap()
+    );
+}
+
+#[test]
+fn generate_song_with_options() {
+    let mut header = get_simple_txt_header();
+    header.title = String::from("Über");
+    header.bpm = 123.5;
+    let lines = get_simple_txt_lines();
+    let options = GeneratorOptions {
+        line_ending: LineEnding::CrLf,
+        encoding: OutputEncoding::Windows1252,
+        tag_order: vec![String::from("ARTIST"), String::from("BPM")],
+        lowercase_switches: true,
+        float_precision: Some(2),
+        decimal_comma: true,
+        ..GeneratorOptions::default()
+    };
+
+    let generated_bytes = generate_song_bytes(&header, &lines, &options).unwrap();
+    assert!(generated_bytes.starts_with(b"#ARTIST:Testartist\r\n#BPM:123,50\r\n#TITLE:\xdcber\r\n"));
+    assert!(contains_bytes(
+        &generated_bytes,
+        b"\r\n#ENCODING:CP1252\r\n"
+    ));
+    assert!(generated_bytes.ends_with(b"\r\nE"));
+
+    let parsed_song = parse_txt_song_reader(generated_bytes.as_slice()).unwrap();
+    assert_eq!(parsed_song.header.title, header.title);
+    assert_eq!(parsed_song.header.bpm, header.bpm);
+    assert_eq!(parsed_song.header.relative, header.relative);
+    assert_eq!(parsed_song.lines, lines);
+
+    let versioned_options = GeneratorOptions {
+        version: Some(Version::V1_0_0),
+        ..options.clone()
+    };
+    assert_error_kind!(
+        generate_song_bytes(&header, &lines, &versioned_options)
+            .err()
+            .unwrap(),
+        ultrastar_txt::generator::ErrorKind::UnsupportedEncoding
+    );
+
+    header.encoding = Some(String::from("CP1252"));
+    let utf8_options = GeneratorOptions {
+        encoding: OutputEncoding::Utf8,
+        ..options
+    };
+    let generated_bytes = generate_song_bytes(&header, &lines, &utf8_options).unwrap();
+    assert!(contains_bytes(&generated_bytes, b"\r\n#ENCODING:UTF8\r\n"));
+}
+
+#[test]
+fn generate_song_with_bom() {
+    let options = GeneratorOptions {
+        bom: true,
+        ..GeneratorOptions::default()
+    };
+    let generated_bytes =
+        generate_song_bytes(&get_simple_txt_header(), &get_simple_txt_lines(), &options).unwrap();
+    assert!(generated_bytes.starts_with(b"\xef\xbb\xbf#TITLE:"));
+    let generated_txt = String::from_utf8(generated_bytes).unwrap();
+    assert_eq!(
+        parse_txt_song_str(generated_txt.as_ref()).unwrap().header,
+        get_simple_txt_header()
+    );
+}
+
+#[test]
+fn document_round_trip() {
+    let txt = include_str!("txts/lossless.txt");
+    let document = TXTDocument::parse(txt);
+    assert_eq!(document.to_string(), txt);
+    assert_eq!(document.tag("BPM"), Some("300,5"));
+    assert_eq!(document.tag("GAP"), Some(""));
+    assert_eq!(
+        document.tags().iter().map(|x| x.0).collect::<Vec<_>>(),
+        vec!["TITLE", "ARTIST", "MYTAG", "MP3", "BPM", "GAP", "ZZZ"]
+    );
+    assert_eq!(document.to_song().unwrap().header.bpm, 300.5);
+}
+
+#[test]
+fn document_edit_tag() {
+    let txt = include_str!("txts/lossless.txt");
+    let mut document = TXTDocument::parse(txt);
+    document.set_tag("TITLE", "Edited");
+    document.set_tag("YEAR", "2001");
+    assert_eq!(document.remove_tag("MYTAG"), Some(String::from("first")));
+    assert_eq!(
+        document.to_string(),
+        txt.replace("#TITLE:Lossless", "#TITLE:Edited")
+            .replace("#MYTAG:first\r\n", "")
+            .replace("#ZZZ:second\r\n", "#ZZZ:second\r\n#YEAR:2001\r\n")
+    );
+    assert_eq!(document.to_song().unwrap().header.year, Some(2001));
+}
+
+#[test]
+fn document_edit_note() {
+    let txt = include_str!("txts/lossless.txt");
+    let mut document = TXTDocument::parse(txt);
+    assert_eq!(
+        document.note(1).unwrap(),
+        Note::Golden {
+            start: 4,
+            duration: 4,
+            pitch: 7,
+            text: String::from("lo"),
+        }
+    );
+    let note = Note::Regular {
+        start: 4,
+        duration: 6,
+        pitch: 8,
+        text: String::from("lo"),
+    };
+    document.set_note(1, &note).unwrap();
+    assert_eq!(
+        document.to_string(),
+        txt.replace("* 4 4 7 lo", ": 4 6 8 lo")
+    );
+    assert_eq!(document.to_song().unwrap().lines[0].notes[1], note);
+    assert_error_kind!(
+        document.note(3).unwrap_err(),
+        ultrastar_txt::document::ErrorKind::NoteNotFound(3)
+    );
+}
+
+#[test]
+fn timing_of_notes() {
+    let txt = include_str!("txts/timing.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    assert_eq!(song.line_offset(0), 0);
+    assert_eq!(song.line_offset(1), 10);
+    assert_eq!(
+        song.note_time_ms(0, &song.lines[0].notes[0]),
+        Some((1000.0, 1200.0))
+    );
+    assert_eq!(
+        song.note_time_ms(1, &song.lines[1].notes[0]),
+        Some((1500.0, 1700.0))
+    );
+    assert_eq!(
+        song.note_time_ms(1, &song.lines[1].notes[2]),
+        Some((2200.0, 2600.0))
+    );
+    assert_eq!(song.note_time_ms(1, &song.lines[1].notes[1]), None);
+}
+
+#[test]
+fn timing_beats_and_ms() {
+    let txt = include_str!("txts/timing.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    assert_eq!(song.ms_at_beat(-2.0), 900.0);
+    assert_eq!(song.ms_at_beat(21.5), 2150.0);
+    assert_eq!(song.beat_at_ms(500.0), -10.0);
+    assert_eq!(song.beat_at_ms(1500.0), 10.0);
+    assert_eq!(song.beat_at_ms(2200.0), 22.0);
+    assert_eq!(song.playback_range_ms(), (500.0, Some(9000.0)));
+    assert!(!song.is_played_at_ms(400.0));
+    assert!(song.is_played_at_ms(2200.0));
+    assert!(!song.is_played_at_ms(9001.0));
+}
+
+#[test]
+fn relative_song_to_absolute() {
+    let txt = include_str!("txts/relative_variable_bpm.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    let absolute_song = song.to_absolute();
+    assert_eq!(absolute_song.header.relative, None);
+    assert_eq!(
+        absolute_song.lines[1],
+        Line {
+            start: 20,
+            rel: None,
+            notes: vec![
+                Note::BpmChange {
+                    beat: 26,
+                    bpm: 246.0,
+                },
+                Note::Regular {
+                    start: 24,
+                    duration: 4,
+                    pitch: 59,
+                    text: String::from("Test "),
+                },
+            ],
+        }
+    );
+    assert_eq!(absolute_song.tempo_map(), song.tempo_map());
+    assert_eq!(absolute_song.to_relative(), song);
+}
+
+#[test]
+fn absolute_song_to_relative() {
+    let txt = include_str!("txts/timing.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    let absolute_song = song.to_absolute();
+    assert_eq!(absolute_song.to_absolute(), absolute_song);
+    assert_eq!(absolute_song.to_relative(), song);
+    assert_eq!(absolute_song.ms_at_beat(22.0), song.ms_at_beat(22.0));
+    let generated_txt = generate_song_txt(&absolute_song.header, &absolute_song.lines).unwrap();
+    assert!(generated_txt.contains("- 8\n"));
+    assert!(generated_txt.contains(": 22 4 0 c\n"));
+}
+
+#[test]
+fn relative_line_break_at_origin() {
+    // the last line break is at the origin of the previous line, after a note without duration
+    let txt = "#TITLE:Test\n#ARTIST:Test\n#MP3:Test.mp3\n#BPM:100\n\
+               : 0 2 0 a\n- 2\n: 4 0 0 b\n- 4\n: 6 2 0 c\nE";
+    let song = parse_txt_song_str(txt).unwrap();
+    let relative_song = song.to_relative();
+    assert_eq!(relative_song.lines[2].start, 0);
+    let generated_txt = generate_song_txt(&relative_song.header, &relative_song.lines).unwrap();
+    assert!(generated_txt.contains("- 0 2\n"));
+    let parsed_song = parse_txt_song_str(&generated_txt).unwrap();
+    assert_eq!(parsed_song, relative_song);
+    assert_eq!(parsed_song.to_absolute(), song);
+}
+
+#[test]
+fn duet_tracks() {
+    let txt = include_str!("txts/duet.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    let duet = song.to_duet().unwrap();
+    assert_eq!(duet.header.duet_singer_p1, None);
+    assert_eq!(duet.p1.singer, Some(String::from("Singer One")));
+    assert_eq!(duet.p2.singer, Some(String::from("Singer Two")));
+    assert_eq!(duet.both.singer, None);
+    assert_eq!(
+        duet.p2.lines,
+        vec![
+            Line {
+                start: 0,
+                rel: None,
+                notes: vec![Note::Regular {
+                    start: 2,
+                    duration: 4,
+                    pitch: 60,
+                    text: String::from("Three"),
+                }],
+            },
+            Line {
+                start: 8,
+                rel: None,
+                notes: vec![Note::Regular {
+                    start: 10,
+                    duration: 4,
+                    pitch: 60,
+                    text: String::from("four"),
+                }],
+            },
+        ]
+    );
+    assert_eq!(duet.both.lines.len(), 1);
+    assert_eq!(duet.sung_by(Player::Two).len(), 3);
+    assert_eq!(duet.sung_by(Player::Both).len(), 1);
+    assert_eq!(duet.to_song(), song);
+    assert_eq!(generate_duet_txt(&duet).unwrap(), txt);
+}
+
+#[test]
+fn duet_to_txt_layout() {
+    let txt = include_str!("txts/survive_duett_tags.txt");
+    let duet = parse_txt_song_str(txt).unwrap().to_duet().unwrap();
+    assert_eq!(duet.p1.lines.len(), 1);
+    assert_eq!(duet.p2.lines.len(), 1);
+
+    let song = duet.to_song();
+    let generated_txt = generate_song_txt(&song.header, &song.lines).unwrap();
+    assert!(generated_txt.contains(": 16 4 59 ing.\nP2\n: 24 4 59 Test \n"));
+}
+
+#[test]
+fn not_a_duet() {
+    let song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    assert!(!song.is_duet());
+    assert_eq!(song.to_duet(), None);
+}
+
+#[test]
+fn validate_song() {
+    let txt = include_str!("txts/invalid_notes.txt");
+    let song = parse_txt_song_str(txt).unwrap();
+    let lints: Vec<(&str, Severity, usize, Option<usize>)> = validate(&song)
+        .iter()
+        .map(|lint| (lint.kind.code(), lint.severity, lint.line, lint.note))
+        .collect();
+    assert_eq!(
+        lints,
+        vec![
+            ("note-before-gap", Severity::Warning, 0, Some(0)),
+            ("overlapping-notes", Severity::Error, 0, Some(2)),
+            ("notes-out-of-order", Severity::Error, 0, Some(3)),
+            ("line-break-after-note", Severity::Error, 1, Some(0)),
+            ("zero-length-note", Severity::Error, 1, Some(0)),
+            ("pitch-out-of-range", Severity::Warning, 1, Some(1)),
+            ("empty-line", Severity::Warning, 2, None),
+        ]
+    );
+    assert_eq!(
+        validate(&song)[5].to_string(),
+        "[pitch-out-of-range] line 1, note 1: pitch 80 is out of range"
+    );
+}
+
+#[test]
+fn validate_valid_songs() {
+    let song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    assert_eq!(validate(&song), Vec::new());
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    assert_eq!(validate(&song), Vec::new());
+    let song = parse_txt_song_str(include_str!("txts/timing.txt")).unwrap();
+    assert_eq!(validate(&song), Vec::new());
+}
+
+#[test]
+fn autofix_song() {
+    let txt = include_str!("txts/autofix.txt");
+    let mut song = parse_txt_song_str(txt).unwrap();
+    let lints: Vec<(&str, usize, Option<usize>)> = validate(&song)
+        .iter()
+        .map(|lint| (lint.kind.code(), lint.line, lint.note))
+        .collect();
+    assert_eq!(
+        lints,
+        vec![
+            ("trailing-whitespace", 0, Some(1)),
+            ("notes-out-of-order", 0, Some(2)),
+            ("non-standard-continuation", 0, Some(2)),
+            ("trailing-whitespace", 1, Some(0)),
+        ]
+    );
+    let fixes = autofix(&mut song);
+    assert_eq!(
+        fixes,
+        vec![
+            Fix {
+                kind: FixKind::ShortenedNote { from: 4, to: 3 },
+                line: 0,
+                note: Some(0),
+            },
+            Fix {
+                kind: FixKind::NormalizedContinuation,
+                line: 0,
+                note: Some(1),
+            },
+            Fix {
+                kind: FixKind::TrimmedText,
+                line: 0,
+                note: Some(2),
+            },
+            Fix {
+                kind: FixKind::SortedNotes,
+                line: 0,
+                note: None,
+            },
+            Fix {
+                kind: FixKind::TrimmedText,
+                line: 1,
+                note: Some(0),
+            },
+            Fix {
+                kind: FixKind::MovedLineBreak { from: 8, to: 10 },
+                line: 1,
+                note: None,
+            },
+        ]
+    );
+    assert_eq!(validate(&song), Vec::new());
+    assert!(generate_song_txt(&song.header, &song.lines)
+        .unwrap()
+        .ends_with(": 0 3 0 Hel\n: 3 2 0 ~ \n: 6 4 0 lo\n- 10\n: 12 4 0 world\nE"));
+    assert_eq!(autofix(&mut song), Vec::new());
+}
+
+#[test]
+fn autofix_line_break_before_note_end() {
+    let txt = "#TITLE:Test\n#ARTIST:Test\n#MP3:Test.mp3\n#BPM:100\n\
+               : 0 4 0 a\n- 2\n: 6 2 0 b\nE";
+    let mut song = parse_txt_song_str(txt).unwrap();
+    let lints = validate(&song);
+    assert_eq!(lints.len(), 1);
+    assert_eq!(
+        lints[0].to_string(),
+        "[line-break-before-note-end] line 1: line break is before the end of the previous note"
+    );
+    assert_eq!(
+        autofix(&mut song),
+        vec![Fix {
+            kind: FixKind::MovedLineBreak { from: 2, to: 4 },
+            line: 1,
+            note: None,
+        }]
+    );
+    assert_eq!(validate(&song), Vec::new());
+}
+
+#[test]
+fn autofix_keeps_valid_songs() {
+    let mut song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    assert_eq!(autofix(&mut song), Vec::new());
+    assert_eq!(song, parse_txt_song_str(get_simple_txt_str()).unwrap());
+}
+
+#[test]
+fn transpose_song() {
+    let mut song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    song.transpose(-3);
+    assert_eq!(song.lines[0].notes[1].pitch(), Some(56));
+    assert_eq!(song.lines[0].notes[0], Note::PlayerChange { player: 1 });
+
+    song.transpose_player(Player::Two, 5);
+    let duet = song.to_duet().unwrap();
+    assert_eq!(duet.p1.lines[0].notes[0].pitch(), Some(56));
+    assert_eq!(duet.p2.lines[0].notes[0].pitch(), Some(62));
+    assert_eq!(duet.p2.lines[1].notes[0].pitch(), Some(62));
+    assert_eq!(duet.both.lines[0].notes[0].pitch(), Some(58));
+    assert!(generate_song_txt(&song.header, &song.lines)
+        .unwrap()
+        .contains("P2\n: 2 4 62 Three\n"));
+}
+
+#[test]
+fn normalize_octave() {
+    let mut song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    assert_eq!(song.median_pitch(), Some(59));
+    assert_eq!(song.normalize_octave(4), -24);
+    assert_eq!(song.median_pitch(), Some(35));
+    assert_eq!(song.normalize_octave(4), 0);
+    assert_eq!(song.normalize_octave(6), 24);
+    // middle C is pitch 12, the start of octave 3
+    assert_eq!(song.normalize_octave(3), -36);
+    assert_eq!(song.median_pitch(), Some(23));
+}
+
+#[test]
+fn rescale_bpm_keeps_timing() {
+    let song = parse_txt_song_str(include_str!("txts/timing.txt")).unwrap();
+    let mut rescaled_song = song.clone();
+    let report = rescaled_song.rescale_bpm(600.0);
+    assert!(report.is_lossless());
+    assert_eq!(report.factor, 2.0);
+    assert_eq!(rescaled_song.header.bpm, 600.0);
+    assert_eq!(rescaled_song.tempo_map(), vec![(0, 600.0), (40, 300.0)]);
+    for (line_index, line) in song.lines.iter().enumerate() {
+        let rescaled_notes = &rescaled_song.lines[line_index].notes;
+        for (note, rescaled_note) in line.notes.iter().zip(rescaled_notes) {
+            assert_eq!(
+                song.note_time_ms(line_index, note),
+                rescaled_song.note_time_ms(line_index, rescaled_note)
+            );
+        }
+    }
+}
+
+#[test]
+fn rescale_bpm_reports_rounding() {
+    let mut song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    let report = song.rescale_bpm(100.0);
+    assert!(!report.is_lossless());
+    assert!(report.max_error_beats <= 0.5);
+    assert_eq!(report.max_error_ms, report.max_error_beats * 150.0);
+    assert_eq!(song.lines[1].start, 16);
+    assert_eq!(song.lines[0].notes[1].start(), Some(3));
+}
+
+#[test]
+fn rescale_bpm_with_invalid_bpm() {
+    let song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    for bpm in [0.0, -100.0, f32::NAN, f32::INFINITY] {
+        let mut rescaled_song = song.clone();
+        let report = rescaled_song.rescale_bpm(bpm);
+        assert!(!report.is_lossless());
+        assert_eq!(rescaled_song, song);
+    }
+    let mut invalid_song = song.clone();
+    invalid_song.header.bpm = 0.0;
+    let mut rescaled_song = invalid_song.clone();
+    assert!(!rescaled_song.rescale_bpm(100.0).is_lossless());
+    assert_eq!(rescaled_song, invalid_song);
+}
+
+#[test]
+fn shift_gap_and_beats() {
+    let mut song = parse_txt_song_str(include_str!("txts/timing.txt")).unwrap();
+    let original_song = song.clone();
+    song.shift_gap(-100.0);
+    assert_eq!(song.header.gap, Some(900.0));
+
+    song.shift_beats(4);
+    for (line_index, line) in song.lines.iter().enumerate() {
+        let original_notes = &original_song.lines[line_index].notes;
+        for (note, original_note) in line.notes.iter().zip(original_notes) {
+            if let Some((start, end)) = original_song.note_time_ms(line_index, original_note) {
+                assert_eq!(
+                    song.note_time_ms(line_index, note),
+                    Some((start + 100.0, end + 100.0))
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn shift_from_line() {
+    let mut song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    song.shift_from_line(1, -2);
+    assert_eq!(song.lines[0], get_simple_txt_lines()[0]);
+    assert_eq!(song.lines[1].start, 18);
+    assert_eq!(song.lines[1].notes[0].start(), Some(22));
+
+    for txt in [
+        include_str!("txts/timing.txt"),
+        include_str!("txts/relative_variable_bpm.txt"),
+    ]
+    .iter()
+    {
+        let mut song = parse_txt_song_str(txt).unwrap();
+        let mut absolute_song = song.to_absolute();
+        song.shift_from_line(1, 3);
+        absolute_song.shift_from_line(1, 3);
+        assert_eq!(song.to_absolute(), absolute_song);
+    }
+}
+
+#[test]
+fn shift_from_line_ms() {
+    let mut song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    assert_eq!(song.shift_from_line_ms(1, 250.0), 2);
+    assert_eq!(song.lines[1].notes[0].start(), Some(26));
+    assert_eq!(song.shift_from_line_ms(5, 250.0), 0);
+}
+
+#[test]
+fn song_stats() {
+    let song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    let stats = song.stats();
+    assert_eq!(stats.regular_notes, 7);
+    assert_eq!(stats.golden_notes, 1);
+    assert_eq!(stats.freestyle_notes, 2);
+    assert_eq!(stats.note_count(), 10);
+    assert_eq!(stats.golden_ratio, 0.1);
+    assert_eq!(stats.line_count, 2);
+    assert_eq!(stats.min_pitch, Some(59));
+    assert_eq!(stats.max_pitch, Some(59));
+    assert_eq!(stats.median_pitch, Some(59));
+    assert_eq!(stats.max_interval, 0);
+    assert_eq!(stats.duet_balance, None);
+    let (start, _) = song.note_time_ms(0, &song.lines[0].notes[0]).unwrap();
+    let (_, end) = song.note_time_ms(1, &song.lines[1].notes[4]).unwrap();
+    assert_eq!(stats.notes_per_second, 10.0 * 1000.0 / (end - start));
+    assert!(stats.difficulty() >= 0.0 && stats.difficulty() <= 10.0);
+
+    let mut jumping_song = song.clone();
+    jumping_song.lines[0].notes[1] = Note::Regular {
+        start: 4,
+        duration: 4,
+        pitch: 66,
+        text: String::from("I"),
+    };
+    let jumping_stats = jumping_song.stats();
+    assert_eq!(jumping_stats.max_interval, 7);
+    assert_eq!(jumping_stats.average_interval, 2.0);
+    assert!(jumping_stats.difficulty() > stats.difficulty());
+}
+
+#[test]
+fn duet_stats() {
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    let stats = song.stats();
+    assert!((stats.duet_balance.unwrap() - 0.5).abs() < 1e-6);
+    assert_eq!(stats.pitch_range(), 2);
+    // intervals are only counted between notes of the same player
+    assert_eq!(stats.max_interval, 0);
+}
+
+#[test]
+fn lyrics() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    assert_eq!(song.lyrics(), "Hello world\ngoodbye my friend");
+
+    let lyric_lines = song.lyric_lines();
+    assert_eq!(lyric_lines.len(), 2);
+    assert_eq!(lyric_lines[1].line, 1);
+    assert_eq!(lyric_lines[1].start(), 12);
+    assert_eq!(lyric_lines[1].end(), 24);
+    assert_eq!(
+        lyric_lines[0].words,
+        vec![
+            LyricWord {
+                text: String::from("Hello"),
+                start: 0,
+                end: 4,
+            },
+            LyricWord {
+                text: String::from("world"),
+                start: 4,
+                end: 8,
+            },
+        ]
+    );
+
+    let words: Vec<(String, i32, i32)> = song
+        .lyric_words()
+        .into_iter()
+        .map(|word| (word.text, word.start, word.end))
+        .collect();
+    assert_eq!(
+        words[2..],
+        [
+            (String::from("goodbye"), 12, 18),
+            (String::from("my"), 18, 22),
+            (String::from("friend"), 22, 24),
+        ]
+    );
+}
+
+#[test]
+fn lyrics_of_relative_song() {
+    let song = parse_txt_song_str(include_str!("txts/timing.txt")).unwrap();
+    assert_eq!(song.lyrics(), "a\nbc");
+    assert_eq!(song.lyric_words()[1].start, 10);
+    assert_eq!(song.lyric_words()[1].end, 26);
+}
+
+#[test]
+fn lyrics_of_duet() {
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    assert_eq!(song.lyrics(), "One\ntwo\nThree\nfour\nAll");
+    let lyric_lines = song.lyric_lines();
+    let players: Vec<(usize, Player)> = lyric_lines
+        .iter()
+        .map(|line| (line.line, line.player))
+        .collect();
+    assert_eq!(
+        players,
+        vec![
+            (0, Player::One),
+            (1, Player::One),
+            (1, Player::Two),
+            (2, Player::Two),
+            (2, Player::Both),
+        ]
+    );
+}
+
+#[test]
+fn lrc_export() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    assert_eq!(
+        generate_lrc(&song),
+        "[ar:Testartist]\n[ti:Lyrics]\n\
+         [00:00.00]Hello world\n\
+         [00:01.80]goodbye my friend\n\
+         [00:03.60]\n"
+    );
+
+    let mut song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    song.lines[1].notes[0] = Note::Regular {
+        start: 8,
+        duration: 40,
+        pitch: 59,
+        text: String::from("two"),
+    };
+    assert!(generate_lrc(&song).ends_with(
+        "[00:00.67]One\n[00:00.91]Three\n[00:01.64]two\n[00:01.89]four\n[00:03.11]All\n\
+         [00:06.52]\n"
+    ));
+}
+
+#[test]
+fn enhanced_lrc_export() {
+    let mut song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    song.header.gap = Some(61_000.0);
+    song.header.creator = Some(String::from("Testcreator"));
+    assert_eq!(
+        generate_enhanced_lrc(&song),
+        "[ar:Testartist]\n[ti:Lyrics]\n[by:Testcreator]\n\
+         [01:01.00]<01:01.00>Hello <01:01.60>world<01:02.20>\n\
+         [01:02.80]<01:02.80>goodbye <01:03.70>my <01:04.30>friend<01:04.60>\n\
+         [01:04.60]\n"
+    );
+}
+
+#[test]
+fn lrc_import() {
+    let lrc = include_str!("txts/import.lrc");
+    let options = LrcOptions {
+        bpm: 100.0,
+        ..LrcOptions::default()
+    };
+    let song = parse_lrc_str(lrc, &options).unwrap();
+    assert_eq!(song.header.artist, "Testartist");
+    assert_eq!(song.header.title, "Imported");
+    assert_eq!(song.header.gap, Some(1000.0));
+    assert_eq!(song.lyrics(), "Hello world\ngoodbye my friend");
+    assert_eq!(
+        generate_song_txt(&song.header, &song.lines).unwrap(),
+        "#TITLE:Imported\n#ARTIST:Testartist\n#MP3:Testartist - Imported.mp3\n\
+         #BPM:100\n#GAP:1000\n\
+         F 0 5 0 Hello \nF 5 5 0 world\n- 10\n\
+         F 10 3 0 good\nF 13 3 0 bye \nF 16 3 0 my \nF 19 3 0 friend\nE"
+    );
+    assert_eq!(validate(&song), Vec::new());
+}
+
+#[test]
+fn lrc_round_trip() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    let options = LrcOptions {
+        bpm: 100.0,
+        freestyle: false,
+        pitch: 0,
+    };
+    let imported_song = parse_lrc_str(&generate_enhanced_lrc(&song), &options).unwrap();
+    assert_eq!(imported_song.lyric_lines(), song.lyric_lines());
+    assert_eq!(imported_song.lines[0].notes[0].pitch(), Some(0));
+}
+
+#[test]
+fn lrc_import_errors() {
+    let options = LrcOptions::default();
+    assert_error_kind!(
+        parse_lrc_str("[ar:Test]\n", &options).unwrap_err(),
+        ultrastar_txt::lrc::ErrorKind::MissingLyrics
+    );
+    assert_error_kind!(
+        parse_lrc_str("[ar:Test]\n[00:xx]Hello\n", &options).unwrap_err(),
+        ultrastar_txt::lrc::ErrorKind::InvalidTimestamp(2)
+    );
+    for bpm in [0.0, f32::NAN, f32::INFINITY].iter() {
+        let options = LrcOptions {
+            bpm: *bpm,
+            ..LrcOptions::default()
+        };
+        assert_error_kind!(
+            parse_lrc_str("[00:01.00]hello world", &options).unwrap_err(),
+            ultrastar_txt::lrc::ErrorKind::InvalidBpm
+        );
+    }
+}
+
+#[test]
+fn lrc_import_with_invalid_offset() {
+    let options = LrcOptions::default();
+    for offset in ["nan", "inf", "-inf", "abc"].iter() {
+        let lrc = format!("[offset:{}]\n[00:01.00]Hello\n", offset);
+        assert_error_kind!(
+            parse_lrc_str(&lrc, &options).unwrap_err(),
+            ultrastar_txt::lrc::ErrorKind::InvalidTimestamp(1)
+        );
+    }
+}
+
+#[test]
+fn srt_export() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    assert_eq!(
+        generate_srt(&song),
+        "1\n00:00:00,000 --> 00:00:01,200\nHello world\n\n\
+         2\n00:00:01,800 --> 00:00:03,600\ngoodbye my friend\n\n"
+    );
+
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    let srt = generate_srt(&song);
+    assert!(srt.contains("\n2\n00:00:00,910 --> 00:00:01,398\nThree\n"));
+    assert!(srt.contains("\n3\n00:00:01,642 --> 00:00:02,129\ntwo\n"));
+}
+
+#[test]
+fn webvtt_export() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    assert_eq!(
+        generate_webvtt(&song),
+        "WEBVTT\n\n\
+         00:00:00.000 --> 00:00:01.200\nHello world\n\n\
+         00:00:01.800 --> 00:00:03.600\ngoodbye my friend\n\n"
+    );
+
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    let webvtt = generate_webvtt(&song);
+    assert!(webvtt.contains("\n<v Singer One>two\n"));
+    assert!(webvtt.contains("\n<v Singer Two>Three\n"));
+    assert!(webvtt.contains("\n<v Both>All\n"));
+    let starts: Vec<&str> = webvtt
+        .lines()
+        .filter(|line| line.contains(" --> "))
+        .map(|line| &line[..9])
+        .collect();
+    assert_eq!(
+        starts,
+        vec![
+            "00:00:00.",
+            "00:00:00.",
+            "00:00:01.",
+            "00:00:01.",
+            "00:00:03."
+        ]
+    );
+}
+
+#[test]
+fn subtitle_export_escapes_text() {
+    let mut song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    *song.lines[0].notes[0].text_mut().unwrap() = String::from("Rock & <roll> ");
+    *song.lines[1].notes[0].text_mut().unwrap() = String::from("{\\b1}");
+    let webvtt = generate_webvtt(&song);
+    assert!(webvtt.contains("\nRock &amp; &lt;roll&gt; lo world\n"));
+    let ass = generate_ass(&song);
+    assert!(ass.contains("{\\k30}Rock & <roll> {\\k30}lo "));
+    assert!(ass.contains("{\\k30}\\{\\\\b1\\}{\\k30}bye"));
+}
+
+#[test]
+fn ass_export() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    let ass = generate_ass(&song);
+    assert!(ass.starts_with("[Script Info]\nTitle: Testartist - Lyrics\n"));
+    assert!(ass.contains("\nStyle: P2,"));
+    assert!(ass.ends_with(
+        "Dialogue: 0,0:00:00.00,0:00:01.20,P1,,0,0,0,,{\\k30}Hel{\\k30}lo {\\k30}world{\\k30}\n\
+         Dialogue: 0,0:00:01.80,0:00:03.60,P1,,0,0,0,,\
+         {\\k30}good{\\k30}bye{\\k30}{\\k30} my{\\k30} {\\k30}friend\n"
+    ));
+
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    let ass = generate_ass(&song);
+    assert!(ass.contains(",P2,,0,0,0,,{\\k49}Three\n"));
+    assert!(ass.ends_with(",Both,,0,0,0,,{\\k48}All\n"));
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[test]
+fn midi_export() {
+    let song = parse_txt_song_str(get_simple_txt_str()).unwrap();
+    let midi = generate_midi(&song);
+    assert!(midi.starts_with(b"MThd\x00\x00\x00\x06\x00\x01\x00\x02\x01\xe0MTrk"));
+    // tempo of 123 bpm
+    assert!(contains_bytes(&midi, b"\xff\x51\x03\x07\x71\x7d"));
+    // the first note after a gap of 655 ticks with its lyric and a pitch of 59 + 48
+    assert!(contains_bytes(
+        &midi,
+        b"\x00\xff\x03\x06Vocals\x85\x0f\xff\x05\x05Test \x00\x90\x6b\x64"
+    ));
+    // the note off of the first note, followed by the second note
+    assert!(contains_bytes(
+        &midi,
+        b"\x83\x60\x80\x6b\x00\x00\xff\x05\x01I"
+    ));
+    // golden notes have the highest velocity
+    assert!(contains_bytes(&midi, b"\x90\x6b\x7f"));
+    assert!(midi.ends_with(b"\x00\xff\x2f\x00"));
+}
+
+#[test]
+fn midi_export_of_duet() {
+    let song = parse_txt_song_str(include_str!("txts/duet.txt")).unwrap();
+    let midi = generate_midi(&song);
+    assert_eq!(&midi[10..12], b"\x00\x04");
+    assert!(contains_bytes(&midi, b"\xff\x03\x0aSinger One"));
+    assert!(contains_bytes(&midi, b"\xff\x03\x0aSinger Two"));
+    assert!(contains_bytes(&midi, b"\xff\x03\x02P3"));
+    assert!(contains_bytes(&midi, b"\x91\x6c\x64"));
+    assert!(contains_bytes(&midi, b"\x92\x6d\x64"));
+}
+
+#[test]
+fn midi_round_trip() {
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    let options = MidiOptions {
+        bpm: 100.0,
+        ..MidiOptions::default()
+    };
+    let imported_song = parse_midi(&generate_midi(&song), &options).unwrap();
+    assert_eq!(imported_song.header.title, "Lyrics");
+    assert_eq!(imported_song.header.gap, Some(0.0));
+    assert_eq!(imported_song.lyric_lines(), song.lyric_lines());
+    assert_eq!(imported_song.lines[0].notes[0].pitch(), Some(0));
+}
+
+#[test]
+fn midi_import_of_karaoke_file() {
+    let mut track = b"\x00\xff\x01\x09@TKaraoke\x00\xff\x01\x0c@TTestartist".to_vec();
+    track.extend_from_slice(b"\x83\x60\xff\x01\x04\\Hel\x00\x90\x3c\x64");
+    track.extend_from_slice(b"\x81\x70\x80\x3c\x00\x00\xff\x01\x02lo\x00\x90\x3e\x64");
+    track.extend_from_slice(b"\x81\x70\x80\x3e\x00\x00\xff\x01\x06/world\x00\x90\x40\x64");
+    // a note on without velocity after running status ends the last note
+    track.extend_from_slice(b"\x83\x60\x40\x00\x00\xff\x2f\x00");
+    let mut midi = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xe0MTrk".to_vec();
+    midi.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    midi.extend(track);
+
+    let options = MidiOptions {
+        bpm: 120.0,
+        ..MidiOptions::default()
+    };
+    let song = parse_midi(&midi, &options).unwrap();
+    assert_eq!(
+        generate_song_txt(&song.header, &song.lines).unwrap(),
+        "#TITLE:Karaoke\n#ARTIST:Testartist\n#MP3:Testartist - Karaoke.mp3\n\
+         #BPM:120\n#GAP:500\n\
+         : 0 2 12 Hel\n: 2 2 14 lo\n- 4\n: 4 4 16 world\nE"
+    );
+}
+
+#[test]
+fn midi_import_errors() {
+    let options = MidiOptions::default();
+    assert_error_kind!(
+        parse_midi(b"RIFF", &options).unwrap_err(),
+        ultrastar_txt::midi::ErrorKind::InvalidMidi("missing header chunk")
+    );
+    let song = parse_txt_song_str(include_str!("txts/lyrics.txt")).unwrap();
+    let midi = generate_midi(&song);
+    assert_error_kind!(
+        parse_midi(&midi[..midi.len() - 1], &options).unwrap_err(),
+        ultrastar_txt::midi::ErrorKind::InvalidMidi("unexpected end of file")
+    );
+    // the first track only contains the tempo
+    let options = MidiOptions {
+        track: Some(0),
+        ..MidiOptions::default()
+    };
+    assert_error_kind!(
+        parse_midi(&midi, &options).unwrap_err(),
+        ultrastar_txt::midi::ErrorKind::TrackNotFound(0)
+    );
+    for bpm in [0.0, f32::NAN, f32::INFINITY].iter() {
+        let options = MidiOptions {
+            bpm: *bpm,
+            ..MidiOptions::default()
+        };
+        assert_error_kind!(
+            parse_midi(&midi, &options).unwrap_err(),
+            ultrastar_txt::midi::ErrorKind::InvalidBpm
+        );
+    }
+}
+
 fn get_simple_txt_str() -> &'static str {
     include_str!("txts/simple_txt_with_all_features.txt")
 }
@@ -376,6 +1621,19 @@ fn get_simple_txt_header() -> Header {
         edition: Some(String::from("Testmusic")),
         language: Some(String::from("en")),
         year: Some(1337),
+        creator: None,
+        start: None,
+        end: None,
+        resolution: None,
+        notes_gap: None,
+        encoding: None,
+        preview_start: None,
+        medley_start_beat: None,
+        medley_end_beat: None,
+        calc_medley: None,
+        duet_singer_p1: None,
+        duet_singer_p2: None,
+        version: Version::Legacy,
         unknown: None,
     }
 }